@@ -1,4 +1,39 @@
 <?xml version="1.0" encoding="UTF-8"?>
 <tileset version="1.10" tiledversion="1.11.2" name="tileset" tilewidth="8" tileheight="8" tilecount="4096" columns="64">
  <image source="tileset.ase" width="512" height="512"/>
+ <tile id="257">
+  <properties>
+   <property name="slope" value="0,8"/>
+  </properties>
+ </tile>
+ <tile id="258">
+  <properties>
+   <property name="slope" value="8,0"/>
+  </properties>
+ </tile>
+ <tile id="259">
+  <properties>
+   <property name="slope" value="0,4"/>
+  </properties>
+ </tile>
+ <tile id="260">
+  <properties>
+   <property name="slope" value="4,8"/>
+  </properties>
+ </tile>
+ <tile id="261">
+  <properties>
+   <property name="slope" value="8,4"/>
+  </properties>
+ </tile>
+ <tile id="262">
+  <properties>
+   <property name="slope" value="4,0"/>
+  </properties>
+ </tile>
+ <tile id="263">
+  <properties>
+   <property name="platform" type="bool" value="true"/>
+  </properties>
+ </tile>
 </tileset>
@@ -1,3 +1,5 @@
+use std::str::FromStr;
+
 use asefile::AsepriteFile;
 use enum_iterator::Sequence;
 use image::EncodableLayout;
@@ -10,6 +12,9 @@ pub enum AnimationID {
     PlayerIdle,
     PlayerSprint,
     PlayerSlide,
+    PlayerDeath,
+    PlayerWallSlide,
+    PlayerWallJump,
     TestBox,
 }
 impl From<AnimationID> for usize {
@@ -17,6 +22,16 @@ impl From<AnimationID> for usize {
         val as usize
     }
 }
+impl FromStr for AnimationID {
+    type Err = ();
+
+    /// Parses an animation from its variant name, e.g. `PlayerSprint`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        enum_iterator::all::<AnimationID>()
+            .find(|id| format!("{id:?}") == s)
+            .ok_or(())
+    }
+}
 
 /// An immutable collection of all the game's assets.
 ///
@@ -37,6 +52,15 @@ impl Assets {
             AnimationID::PlayerSlide => {
                 Animation::from_file(include_bytes!("../assets/entities/player/slide.ase"))
             }
+            AnimationID::PlayerDeath => {
+                Animation::from_file(include_bytes!("../assets/entities/player/death.ase"))
+            }
+            AnimationID::PlayerWallSlide => {
+                Animation::from_file(include_bytes!("../assets/entities/player/wall_slide.ase"))
+            }
+            AnimationID::PlayerWallJump => {
+                Animation::from_file(include_bytes!("../assets/entities/player/wall_jump.ase"))
+            }
             AnimationID::TestBox => {
                 Animation::from_file(include_bytes!("../assets/entities/enemy.ase"))
             }
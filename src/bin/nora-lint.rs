@@ -0,0 +1,105 @@
+//! Checks every screen for problems, without opening a window.
+//!
+//! Every map in `assets/screens` is checked, whether the game loads it or not.
+//! Exits with a failure if there are any errors. Warnings alone don't fail.
+
+use std::{fs, io, process::ExitCode};
+
+use nora::{screens::*, utils::*};
+
+/// Where the screen files are, which [SCREEN_FILES] should list all of
+const SCREENS_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/screens");
+
+/// Every `.tmx` file in the screens folder, by name, with its contents.
+fn screen_files() -> io::Result<Vec<(String, String)>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(SCREENS_DIR)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|extension| extension == "tmx") {
+            let name = path.file_name().unwrap().to_string_lossy().into_owned();
+            files.push((name, fs::read_to_string(&path)?));
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn main() -> ExitCode {
+    let mut errors = 0;
+    let mut warnings = 0;
+    // links can only be checked between screens that load
+    let mut all_loaded = true;
+
+    let files = match screen_files() {
+        Ok(files) => files,
+        Err(e) => {
+            eprintln!("error: failed to read {SCREENS_DIR}: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    for (file, data) in &files {
+        if !SCREEN_FILES.iter().any(|(name, _)| name == file) {
+            eprintln!("error: {file} isn't in SCREEN_FILES, so the game never loads it");
+            errors += 1;
+        }
+        let map = match Map::from_file((file, data)) {
+            Ok(map) => map,
+            Err(e) => {
+                eprintln!("error: {e}");
+                errors += 1;
+                all_loaded = false;
+                continue;
+            }
+        };
+        for (layer, tiles) in map.tile_layers() {
+            for (index, tile) in tiles.iter().enumerate() {
+                if *tile > TILESET_TILES {
+                    eprintln!(
+                        "error: {file} (layer \"{layer}\"): tile {tile} at ({}, {}) isn't in the tileset",
+                        index % map.width,
+                        index / map.width
+                    );
+                    errors += 1;
+                }
+            }
+        }
+        // entities and map properties are only read when the map becomes a screen
+        if let Err(e) = load_screen((file, data)) {
+            eprintln!("error: {e}");
+            errors += 1;
+            all_loaded = false;
+        }
+    }
+
+    if !all_loaded {
+        eprintln!("skipping link checks, as not every screen loaded");
+    } else {
+        match create_screen_registry() {
+            Ok(screens) => {
+                let start = start_screen();
+                for (screen, problem) in validate_screens(&screens, &start) {
+                    eprintln!("error: screen \"{screen}\": {problem}");
+                    errors += 1;
+                }
+                for screen in unreachable_screens(&screens, &start) {
+                    eprintln!("warning: screen \"{screen}\" can't be reached from the start");
+                    warnings += 1;
+                }
+            }
+            Err(e) => {
+                eprintln!("error: {e}");
+                errors += 1;
+            }
+        }
+    }
+
+    println!(
+        "checked {} screens: {errors} errors, {warnings} warnings",
+        files.len()
+    );
+    if errors > 0 {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
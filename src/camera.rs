@@ -0,0 +1,80 @@
+use macroquad::prelude::*;
+
+use crate::utils::*;
+
+/// Follows a target around a map. Drives the cameras of every render layer but the ui.
+pub struct FollowCamera {
+    /// Centre of the view, in world pixels
+    pub pos: Vec2,
+    /// Centre of the view at the previous tick
+    pub last_pos: Vec2,
+    /// How far the target may stray from the centre on each axis before the camera moves
+    pub dead_zone: Vec2,
+    /// How far ahead of the target to look in the direction it's facing
+    pub look_ahead: f32,
+    /// Fraction of the remaining look-ahead shift applied per tick when turning around
+    pub look_ahead_speed: f32,
+    /// Area the view is kept within, usually the whole map
+    pub bounds: Rect,
+    look_offset: f32,
+}
+impl FollowCamera {
+    pub fn new(bounds: Rect) -> Self {
+        Self {
+            pos: bounds.center(),
+            last_pos: bounds.center(),
+            dead_zone: Vec2::new(0.0, 3.0 * 8.0),
+            look_ahead: 3.0 * 8.0,
+            look_ahead_speed: 0.05,
+            bounds,
+            look_offset: 0.0,
+        }
+    }
+    /// Moves straight to the target, e.g. when a screen is loaded.
+    pub fn snap(&mut self, target: Vec2, facing_right: bool) {
+        self.look_offset = self.look_ahead * if facing_right { 1.0 } else { -1.0 };
+        self.pos = target + Vec2::new(self.look_offset, 0.0);
+        self.clamp();
+        self.last_pos = self.pos;
+    }
+    pub fn update(&mut self, target: Vec2, facing_right: bool) {
+        self.last_pos = self.pos;
+        let look_target = self.look_ahead * if facing_right { 1.0 } else { -1.0 };
+        self.look_offset += (look_target - self.look_offset) * self.look_ahead_speed;
+
+        let focus = target + Vec2::new(self.look_offset, 0.0);
+        let delta = focus - self.pos;
+        if delta.x.abs() > self.dead_zone.x {
+            self.pos.x += delta.x - self.dead_zone.x * delta.x.signum();
+        }
+        if delta.y.abs() > self.dead_zone.y {
+            self.pos.y += delta.y - self.dead_zone.y * delta.y.signum();
+        }
+        self.clamp();
+    }
+    /// Keeps the view inside the bounds, centring it on any axis where the bounds are smaller.
+    fn clamp(&mut self) {
+        let half = Vec2::new(SCREEN_WIDTH, SCREEN_HEIGHT) / 2.0;
+        let min = self.bounds.point() + half;
+        let max = self.bounds.point() + self.bounds.size() - half;
+        let center = self.bounds.center();
+        self.pos.x = if min.x > max.x {
+            center.x
+        } else {
+            self.pos.x.clamp(min.x, max.x)
+        };
+        self.pos.y = if min.y > max.y {
+            center.y
+        } else {
+            self.pos.y.clamp(min.y, max.y)
+        };
+    }
+    /// The visible area, snapped to whole pixels.
+    ///
+    /// `alpha` is how far between the previous and current tick to draw it.
+    pub fn view(&self, alpha: f32) -> Rect {
+        let pos = self.last_pos.lerp(self.pos, alpha);
+        let top_left = (pos - Vec2::new(SCREEN_WIDTH, SCREEN_HEIGHT) / 2.0).floor();
+        Rect::new(top_left.x, top_left.y, SCREEN_WIDTH, SCREEN_HEIGHT)
+    }
+}
@@ -0,0 +1,100 @@
+use std::{fs, time::SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+/// The movement tuning built into the game, and where debug builds reload it from.
+pub const MOVEMENT_CONFIG_FILE: (&str, &str) = (
+    concat!(env!("CARGO_MANIFEST_DIR"), "/assets/movement.toml"),
+    include_str!("../assets/movement.toml"),
+);
+
+/// Numbers that decide how movement feels, for the player, enemies, and the physics every
+/// entity shares. See `assets/movement.toml` for what each one does.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct MovementConfig {
+    pub gravity: f32,
+    pub ground_friction: f32,
+    pub air_drag: f32,
+    pub step_height: f32,
+    pub stop_speed: f32,
+
+    pub run_force: f32,
+    pub max_run_velocity: f32,
+
+    pub slide_boost: f32,
+    pub slide_friction: f32,
+    pub slope_slide_acceleration: f32,
+    pub slide_landing_boost: f32,
+    pub slide_landing_soft_boost: f32,
+    pub max_slide_landing_boost: f32,
+    pub stuck_slide_speed: f32,
+    pub stuck_slide_force: f32,
+    pub slide_jump_full_speed: f32,
+
+    pub jump_impulse: f32,
+    pub jump_hold_force: f32,
+    pub jump_hold_ticks: u8,
+    pub coyote_time: u32,
+    pub jump_buffer: u32,
+
+    pub wall_slide_speed: f32,
+    pub wall_jump_kick: f32,
+    pub wall_jump_lockout: u32,
+
+    pub enemy_jump_impulse: f32,
+    pub enemy_air_force: f32,
+}
+impl MovementConfig {
+    pub fn parse(text: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(text)
+    }
+    /// The tuning the game was built with.
+    pub fn built_in() -> Self {
+        Self::parse(MOVEMENT_CONFIG_FILE.1).expect("built-in movement config should be valid")
+    }
+}
+
+/// Reloads the movement config whenever its file changes on disk.
+///
+/// Only debug builds look at the file. Release builds, and anywhere without a
+/// filesystem, keep the built-in config.
+pub struct ConfigWatcher {
+    /// When the file was last changed, as of the last check
+    modified: Option<SystemTime>,
+}
+impl ConfigWatcher {
+    pub fn new() -> Self {
+        Self { modified: None }
+    }
+    /// Replaces `config` with the file's contents if it changed since the last call.
+    ///
+    /// A file that doesn't parse is reported and otherwise ignored.
+    pub fn reload(&mut self, config: &mut MovementConfig) {
+        if !cfg!(debug_assertions) {
+            return;
+        }
+        let path = MOVEMENT_CONFIG_FILE.0;
+        let Ok(modified) = fs::metadata(path).and_then(|m| m.modified()) else {
+            return;
+        };
+        if self.modified == Some(modified) {
+            return;
+        }
+        self.modified = Some(modified);
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) => {
+                eprintln!("failed to read {path}: {e}");
+                return;
+            }
+        };
+        match MovementConfig::parse(&text) {
+            Ok(new) => {
+                *config = new;
+                println!("loaded movement config from {path}");
+            }
+            Err(e) => eprintln!("failed to load movement config {path}: {e}"),
+        }
+    }
+}
@@ -0,0 +1,115 @@
+//! An overlay showing what the engine sees: collision, special tiles, hitboxes and the
+//! player's physics state. Toggled with F3.
+
+use macroquad::prelude::*;
+
+use crate::{
+    entity::NonPlayerEntity,
+    graphics::{DrawCall, RenderLayer},
+    player::Player,
+    screens::{CollisionTile, Map},
+};
+
+/// How much longer velocity vectors are drawn than a tick's movement
+const VELOCITY_SCALE: f32 = 4.0;
+
+/// Colour a special tile is highlighted with, if it's one the engine uses.
+fn special_tile_color(tile: usize) -> Option<Color> {
+    match tile {
+        // jump hints
+        1..=3 => Some(YELLOW),
+        // exits
+        4..=7 => Some(GREEN),
+        // spawns
+        8..=11 => Some(SKYBLUE),
+        // reset
+        12 => Some(RED),
+        // checkpoint
+        13 => Some(VIOLET),
+        _ => None,
+    }
+}
+
+/// Draws the overlay onto the ui layer, for the part of the map in `view`.
+pub fn draw_overlay(
+    layer: &mut RenderLayer,
+    view: Rect,
+    map: &Map,
+    entities: &[Box<dyn NonPlayerEntity>],
+    player: &Player,
+) {
+    // the ui layer doesn't follow the camera, so everything is moved into view by hand
+    let offset = -view.point();
+
+    let min_x = (view.left() / 8.0).floor().max(0.0) as usize;
+    let min_y = (view.top() / 8.0).floor().max(0.0) as usize;
+    let max_x = ((view.right() / 8.0).ceil().max(0.0) as usize).min(map.width);
+    let max_y = ((view.bottom() / 8.0).ceil().max(0.0) as usize).min(map.height);
+    for y in min_y..max_y {
+        for x in min_x..max_x {
+            let (left, top) = (x as f32 * 8.0 + offset.x, y as f32 * 8.0 + offset.y);
+            if let Some(color) = special_tile_color(map.get_special_tile(x, y)) {
+                layer
+                    .calls
+                    .push(DrawCall::Rect(left, top, 8.0, 8.0, color.with_alpha(0.5)));
+            }
+            let color = ORANGE.with_alpha(0.6);
+            match map.get_collision_tile(x, y) {
+                CollisionTile::Empty => {}
+                CollisionTile::Solid => {
+                    layer
+                        .calls
+                        .push(DrawCall::RectLines(left, top, 8.0, 8.0, color));
+                }
+                CollisionTile::Slope(l, r) => {
+                    layer.calls.push(DrawCall::Line(
+                        left,
+                        top + 8.0 - l,
+                        left + 8.0,
+                        top + 8.0 - r,
+                        color,
+                    ));
+                }
+                CollisionTile::Platform => {
+                    layer
+                        .calls
+                        .push(DrawCall::Line(left, top, left + 8.0, top, color));
+                }
+            }
+        }
+    }
+
+    let bodies = entities
+        .iter()
+        .filter_map(|entity| Some((entity.hitbox()?, entity.velocity())))
+        .chain(std::iter::once((player.hitbox(), player.velocity)));
+    for (hitbox, velocity) in bodies {
+        let hitbox = hitbox.offset(offset);
+        layer.calls.push(DrawCall::RectLines(
+            hitbox.x, hitbox.y, hitbox.w, hitbox.h, MAGENTA,
+        ));
+        let center = hitbox.center();
+        let end = center + velocity * VELOCITY_SCALE;
+        layer
+            .calls
+            .push(DrawCall::Line(center.x, center.y, end.x, end.y, LIME));
+    }
+
+    let lines = [
+        format!("pos: {:.2}, {:.2}", player.pos.x, player.pos.y),
+        format!(
+            "velocity: {:.2}, {:.2}",
+            player.velocity.x, player.velocity.y
+        ),
+        format!("on_ground: {}", player.on_ground),
+        format!("head_covered: {}", player.head_covered),
+        format!("standing: {}", player.standing),
+        format!("jump_frames: {}", player.jump_frames),
+        format!("wall: {} {}", player.wall_left, player.wall_right),
+    ];
+    for (i, line) in lines.into_iter().enumerate() {
+        layer
+            .calls
+            .push(DrawCall::Text(line, 4.0, 20.0 + i as f32 * 10.0, WHITE));
+    }
+}
@@ -2,184 +2,1820 @@ use macroquad::prelude::*;
 
 use crate::{
     assets::AnimationID,
+    config::MovementConfig,
     graphics::DrawCall,
-    screens::{Map, ScreenUpdateContext},
-    utils::*,
+    nav::{self, JumpArc, Move, NavGraph, Step, Tile},
+    screens::{CollisionTile, Map, ScreenUpdateContext},
+    tmx::{Object, TmxErrorKind},
 };
 
-fn ceil_g(a: f32) -> f32 {
-    if a < 0.0 { a.floor() } else { a.ceil() }
-}
-
 #[expect(unused_variables)]
 pub trait NonPlayerEntity {
-    fn update(&mut self, map: &Map, ctx: &mut ScreenUpdateContext) {}
-    fn draw(&self, ctx: &mut ScreenUpdateContext) {}
+    /// Moves entities that others collide with, like moving platforms, before anything
+    /// else updates. Returns the solid the entity now is, if it is one.
+    fn update_solid(&mut self) -> Option<Solid> {
+        None
+    }
+    fn update(&mut self, world: &World, ctx: &mut ScreenUpdateContext) {}
+    /// Whether the entity finds its way around with [World::nav], so the screen needs to
+    /// build it.
+    fn navigates(&self) -> bool {
+        false
+    }
+    /// Whether the entity is gone for good, like after being crushed.
+    fn removed(&self) -> bool {
+        false
+    }
+    /// The entity's hitbox in world pixels, if it has one, for the debug overlay.
+    fn hitbox(&self) -> Option<Rect> {
+        None
+    }
+    fn velocity(&self) -> Vec2 {
+        Vec2::ZERO
+    }
+    fn draw(&self, ctx: &mut ScreenUpdateContext, alpha: f32) {}
 }
-pub struct HumanoidEnemy {
+/// Creates the entity described by a Tiled object, based on its type.
+///
+/// The object's position is used as the entity's position.
+pub fn from_object(object: &Object) -> Result<Box<dyn NonPlayerEntity>, TmxErrorKind> {
+    let pos = Vec2::new(object.x, object.y);
+    match object.class.as_str() {
+        "HumanoidEnemy" => Ok(Box::new(HumanoidEnemy::new(
+            pos,
+            object.property("animation", AnimationID::PlayerSprint)?,
+            object.property("speed", 0.4)?,
+        ))),
+        "Patroller" => Ok(Box::new(Patroller::new(
+            pos,
+            object.property("animation", AnimationID::TestBox)?,
+            object.property("facing_right", true)?,
+            object.property("speed", 0.5)?,
+            object.property("damage", 10.0)?,
+        ))),
+        "Flyer" => {
+            let pattern = match object.property("pattern", "sine".to_string())?.as_str() {
+                "sine" => {
+                    let amplitude = object.property("amplitude", 16.0)?;
+                    let period: f32 = object.property("period", 120.0)?;
+                    // the time is divided by the period, so it needs to be positive
+                    if period <= 0.0 {
+                        return Err(TmxErrorKind::InvalidProperty {
+                            object: object.id,
+                            name: "period".to_string(),
+                            value: period.to_string(),
+                        });
+                    }
+                    FlightPattern::Sine { amplitude, period }
+                }
+                "chase" => FlightPattern::Chase {
+                    range: object.property("range", 96.0)?,
+                },
+                other => {
+                    return Err(TmxErrorKind::InvalidProperty {
+                        object: object.id,
+                        name: "pattern".to_string(),
+                        value: other.to_string(),
+                    });
+                }
+            };
+            Ok(Box::new(Flyer::new(
+                pos,
+                object.property("animation", AnimationID::TestBox)?,
+                pattern,
+                object.property("speed", 0.5)?,
+                object.property("damage", 10.0)?,
+            )))
+        }
+        "Turret" => Ok(Box::new(Turret::new(
+            pos,
+            object.property("animation", AnimationID::TestBox)?,
+            object.property("interval", 90)?,
+            object.property("range", 128.0)?,
+            object.property("projectile_speed", 2.0)?,
+            object.property("damage", 10.0)?,
+        ))),
+        "MovingPlatform" => {
+            // the path comes from a polyline or polygon, without which there's nowhere to go
+            if object.points.is_empty() {
+                return Err(TmxErrorKind::InvalidProperty {
+                    object: object.id,
+                    name: "points".to_string(),
+                    value: String::new(),
+                });
+            }
+            let path = object
+                .points
+                .iter()
+                .map(|&(x, y)| pos + Vec2::new(x, y))
+                .collect();
+            Ok(Box::new(MovingPlatform::new(
+                path,
+                object.closed,
+                Vec2::new(
+                    object.property("width", 24.0)?,
+                    object.property("height", 8.0)?,
+                ),
+                object.property("speed", 0.5)?,
+            )))
+        }
+        _ => Err(TmxErrorKind::UnknownObjectType {
+            object: object.id,
+            class: object.class.clone(),
+        }),
+    }
+}
+
+/// Where a walking enemy is and how it's moving, with a tall hitbox under gravity.
+pub struct Body {
     pub pos: Vec2,
+    pub last_pos: Vec2,
     pub velocity: Vec2,
+    pub on_ground: bool,
+    /// Whether a solid crushed it, which removes the entity
+    pub crushed: bool,
+}
+impl Body {
+    pub fn new(pos: Vec2) -> Self {
+        Self {
+            pos,
+            last_pos: pos,
+            velocity: Vec2::ZERO,
+            on_ground: false,
+            crushed: false,
+        }
+    }
+    pub fn hitbox(&self) -> Rect {
+        TALL_HITBOX.offset(self.pos)
+    }
+    /// Drag slowing the body down sideways, which is stronger on the ground.
+    fn drag(&self, config: &MovementConfig) -> f32 {
+        if self.on_ground {
+            config.ground_friction
+        } else {
+            config.air_drag
+        }
+    }
+    /// Moves the body one tick with [update_physics_entity], keeping track of whether
+    /// it's on the ground or crushed.
+    fn step(&mut self, forces: &mut Vec2, drop_through: bool, world: &World) -> Contacts {
+        self.last_pos = self.pos;
+        let contacts = update_physics_entity(
+            &mut self.pos,
+            forces,
+            &mut self.velocity,
+            TALL_HITBOX,
+            drop_through,
+            world,
+        );
+        self.on_ground = contacts.on_ground();
+        self.crushed = contacts.crushed;
+        contacts
+    }
+}
+
+/// Draws an entity's animation at `pos`, the top left of its hitbox, with the hitbox
+/// at the bottom middle of the sprite.
+fn draw_animation(
+    ctx: &mut ScreenUpdateContext,
+    animation: AnimationID,
+    frame: u32,
+    pos: Vec2,
+    flip_x: bool,
+) {
+    let pos = pos.floor();
+    ctx.render_layers.entities.calls.push(DrawCall::Animation(
+        animation,
+        frame,
+        pos.x - 4.0,
+        pos.y - 8.0,
+        Some(DrawTextureParams {
+            flip_x,
+            ..Default::default()
+        }),
+    ));
+}
+
+/// How humanoid enemies move, for working out where they can jump to.
+pub fn humanoid_arc(config: &MovementConfig) -> JumpArc {
+    JumpArc {
+        impulse: config.enemy_jump_impulse,
+        gravity: config.gravity,
+        force: config.enemy_air_force,
+        air_drag: config.air_drag,
+        run_speed: config.enemy_air_force / config.ground_friction,
+    }
+}
+
+pub struct HumanoidEnemy {
+    pub body: Body,
     pub anim_frame: u32,
     pub animation: AnimationID,
-    pub on_ground: bool,
     pub speed: f32,
+    /// Steps left to get to the player, next first
+    path: Vec<Step>,
+    /// The node the next step of `path` starts from
+    path_start: Option<Tile>,
+    /// The player's node when `path` was found
+    path_goal: Option<Tile>,
 }
 impl HumanoidEnemy {
     pub fn new(pos: Vec2, animation: AnimationID, speed: f32) -> Self {
         Self {
-            pos,
-            velocity: Vec2::ZERO,
+            body: Body::new(pos),
             anim_frame: 0,
             animation,
-            on_ground: false,
             speed,
+            path: Vec::new(),
+            path_start: None,
+            path_goal: None,
+        }
+    }
+    /// Keeps `path` leading to the player, finding a new one when the player moves to
+    /// another tile or this enemy strays from it. Returns the next step, if there's a way.
+    fn follow_path(&mut self, world: &World, player_pos: Vec2) -> Option<Step> {
+        let nav = world.nav?;
+        let goal = nav.node_below(world.map, player_pos);
+        let here = nav.node_below(world.map, self.body.pos);
+        if self.body.on_ground && here.is_some() {
+            if self
+                .path
+                .first()
+                .is_some_and(|step| Some(step.tile) == here)
+            {
+                self.path.remove(0);
+                self.path_start = here;
+            }
+            if here != self.path_start {
+                self.path_goal = None;
+            }
+        }
+        // paths are only found from the ground, where there's a node to start from
+        if goal != self.path_goal && self.body.on_ground {
+            self.path_goal = goal;
+            self.path_start = here;
+            self.path = match (here, goal) {
+                (Some(here), Some(goal)) => nav.find_path(here, goal).unwrap_or_default(),
+                _ => Vec::new(),
+            };
         }
+        self.path.first().copied()
     }
 }
 impl NonPlayerEntity for HumanoidEnemy {
-    fn draw(&self, ctx: &mut ScreenUpdateContext) {
-        ctx.render_layers.entities.calls.push(DrawCall::Animation(
-            self.animation,
-            self.anim_frame,
-            self.pos.floor().x - 4.0,
-            self.pos.floor().y - 8.0,
-            Some(DrawTextureParams {
-                flip_x: self.velocity.x < 0.0,
-                ..Default::default()
-            }),
-        ));
+    fn draw(&self, ctx: &mut ScreenUpdateContext, alpha: f32) {
+        let pos = self.body.last_pos.lerp(self.body.pos, alpha);
+        let flip_x = self.body.velocity.x < 0.0;
+        draw_animation(ctx, self.animation, self.anim_frame, pos, flip_x);
     }
-    fn update(&mut self, map: &Map, ctx: &mut ScreenUpdateContext) {
+    fn update(&mut self, world: &World, ctx: &mut ScreenUpdateContext) {
         self.anim_frame += 1000 / 60;
         let mut forces = Vec2::ZERO;
-        let player_delta = ctx.player.pos - self.pos;
+        let player_delta = ctx.player.pos - self.body.pos;
+
+        let drag = self.body.drag(world.config);
+        // follow the player down through platforms, unless a path says otherwise
+        let mut drop_through = player_delta.y > 8.0;
 
         // move towards player
         if player_delta.length() >= 8.0 {
-            forces.x = player_delta.x;
-            forces = forces.clamp_length_max(1.0) * self.speed;
+            let step = self.follow_path(world, ctx.player.pos);
+            let mut path_jump = false;
+            if let Some(step) = step {
+                let tile_x = |tile: Tile| tile.0 as f32 * 8.0 - self.body.pos.x;
+                // jumps are taken standing still on their start, as the graph expects
+                let start = self.path_start.unwrap_or(step.tile);
+                path_jump = step.movement == Move::Jump
+                    && self.body.on_ground
+                    && tile_x(start).abs() < 1.0
+                    && self.body.velocity.x.abs() < 0.25;
+                let aim = match step.movement {
+                    Move::Jump if self.body.on_ground && !path_jump => start,
+                    // walk straight through runs of walking, without stopping at each tile
+                    Move::Walk => self
+                        .path
+                        .iter()
+                        .take_while(|step| step.movement == Move::Walk)
+                        .last()
+                        .map_or(step.tile, |step| step.tile),
+                    _ => step.tile,
+                };
+                // jumps were worked out steering with the graph's arc
+                let force = if self.body.on_ground {
+                    self.speed
+                } else {
+                    world.config.enemy_air_force
+                };
+                forces.x = nav::steer(tile_x(aim), self.body.velocity.x, force, drag);
+                drop_through = step.movement == Move::DropThrough;
+            } else {
+                // without a way to the player, head straight at them
+                forces.x = player_delta.x.clamp(-1.0, 1.0) * self.speed;
+            }
 
-            // handle special pathfinding
-            let tile = (self.pos / 8.0).round();
-            let tile = map.get_special_tile(tile.x as _, tile.y as _);
+            // jump hint tiles override the path
+            let tile = (self.body.pos / 8.0).round();
+            let tile = world.map.get_special_tile(tile.x as _, tile.y as _);
+            let should_jump = match tile {
+                1 => true,
+                2 => player_delta.x < 0.0,
+                3 => player_delta.x > 0.0,
+                _ => path_jump,
+            };
+            if should_jump && self.body.on_ground {
+                forces.y -= world.config.enemy_jump_impulse;
+            }
+        } else {
+            // attack player
+            ctx.player.damage(10.0, self.body.pos);
+        }
+        forces.x -= self.body.velocity.x * drag;
+        self.body.step(&mut forces, drop_through, world);
+    }
+    fn navigates(&self) -> bool {
+        true
+    }
+    fn removed(&self) -> bool {
+        self.body.crushed
+    }
+    fn hitbox(&self) -> Option<Rect> {
+        Some(self.body.hitbox())
+    }
+    fn velocity(&self) -> Vec2 {
+        self.body.velocity
+    }
+}
 
-            let should_jump = tile != 0
-                && match tile - 1 {
-                    0 => true,
-                    1 => player_delta.x < 0.0,
-                    2 => player_delta.x > 0.0,
+/// A ground enemy that walks back and forth, turning around at walls and ledges.
+pub struct Patroller {
+    pub body: Body,
+    pub anim_frame: u32,
+    pub animation: AnimationID,
+    pub facing_right: bool,
+    pub speed: f32,
+    pub damage: f32,
+}
+impl Patroller {
+    pub fn new(
+        pos: Vec2,
+        animation: AnimationID,
+        facing_right: bool,
+        speed: f32,
+        damage: f32,
+    ) -> Self {
+        Self {
+            body: Body::new(pos),
+            anim_frame: 0,
+            animation,
+            facing_right,
+            speed,
+            damage,
+        }
+    }
+    /// Whether there's floor just past the leading edge of the hitbox, including a ramp
+    /// down.
+    fn floor_ahead(&self, map: &Map) -> bool {
+        let boxed = self.body.hitbox();
+        let x = if self.facing_right {
+            boxed.right() + 0.5
+        } else {
+            boxed.left() - 0.5
+        };
+        let (column, row) = (
+            (x / 8.0).floor() as i32,
+            (boxed.bottom() / 8.0).floor() as i32,
+        );
+        (row..=row + 1).any(|row| map.collision_tile(column, row) != CollisionTile::Empty)
+    }
+}
+impl NonPlayerEntity for Patroller {
+    fn update(&mut self, world: &World, ctx: &mut ScreenUpdateContext) {
+        self.anim_frame += 1000 / 60;
+        if self.body.on_ground && !self.floor_ahead(world.map) {
+            // stops dead, rather than skidding off the edge
+            self.facing_right = !self.facing_right;
+            self.body.velocity.x = 0.0;
+        }
+        let direction = if self.facing_right { 1.0 } else { -1.0 };
+        let drag = self.body.drag(world.config);
+        let mut forces = Vec2::new(direction * self.speed - self.body.velocity.x * drag, 0.0);
+        let contacts = self.body.step(&mut forces, false, world);
+        if (self.facing_right && contacts.wall_right) || (!self.facing_right && contacts.wall_left)
+        {
+            self.facing_right = !self.facing_right;
+        }
+        if overlapping(self.body.hitbox(), ctx.player.hitbox()) {
+            ctx.player.damage(self.damage, self.body.pos);
+        }
+    }
+    fn removed(&self) -> bool {
+        self.body.crushed
+    }
+    fn hitbox(&self) -> Option<Rect> {
+        Some(self.body.hitbox())
+    }
+    fn velocity(&self) -> Vec2 {
+        self.body.velocity
+    }
+    fn draw(&self, ctx: &mut ScreenUpdateContext, alpha: f32) {
+        let pos = self.body.last_pos.lerp(self.body.pos, alpha);
+        draw_animation(
+            ctx,
+            self.animation,
+            self.anim_frame,
+            pos,
+            !self.facing_right,
+        );
+    }
+}
 
-                    _ => false,
+/// How a [Flyer] moves.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FlightPattern {
+    /// Drifts back and forth, bobbing up and down around its home, turning at walls
+    Sine {
+        /// How far above and below its home it bobs, in pixels
+        amplitude: f32,
+        /// Ticks each bob takes
+        period: f32,
+    },
+    /// Flies at the player while they're within `range` pixels, and back home otherwise
+    Chase { range: f32 },
+}
+
+/// A flying enemy, which ignores gravity but not walls.
+pub struct Flyer {
+    pub pos: Vec2,
+    pub last_pos: Vec2,
+    /// Where it was placed
+    pub home: Vec2,
+    pub anim_frame: u32,
+    pub animation: AnimationID,
+    pub pattern: FlightPattern,
+    pub facing_right: bool,
+    /// Pixels moved per tick
+    pub speed: f32,
+    pub damage: f32,
+    /// Ticks since it was placed, for timing its bobbing
+    ticks: u32,
+}
+impl Flyer {
+    pub fn new(
+        pos: Vec2,
+        animation: AnimationID,
+        pattern: FlightPattern,
+        speed: f32,
+        damage: f32,
+    ) -> Self {
+        Self {
+            pos,
+            last_pos: pos,
+            home: pos,
+            anim_frame: 0,
+            animation,
+            pattern,
+            facing_right: true,
+            speed,
+            damage,
+            ticks: 0,
+        }
+    }
+}
+impl NonPlayerEntity for Flyer {
+    fn update(&mut self, world: &World, ctx: &mut ScreenUpdateContext) {
+        self.anim_frame += 1000 / 60;
+        self.last_pos = self.pos;
+        self.ticks += 1;
+        let direction = if self.facing_right { 1.0 } else { -1.0 };
+        let delta = match self.pattern {
+            FlightPattern::Sine { amplitude, period } => {
+                let phase = self.ticks as f32 / period * std::f32::consts::TAU;
+                let y = self.home.y + amplitude * phase.sin();
+                Vec2::new(direction * self.speed, y - self.pos.y)
+            }
+            FlightPattern::Chase { range } => {
+                let to_player =
+                    ctx.player.hitbox().center() - SHORT_HITBOX.offset(self.pos).center();
+                let to = if to_player.length() <= range && !ctx.player.is_dead() {
+                    to_player
+                } else {
+                    self.home - self.pos
                 };
-            if should_jump && self.on_ground {
-                forces.y -= 8.5;
+                to.clamp_length_max(self.speed)
             }
-        } else {
-            // attack player
-            ctx.player.health -= 10.0;
+        };
+        fly(&mut self.pos, SHORT_HITBOX, delta, world);
+        match self.pattern {
+            FlightPattern::Sine { .. } => {
+                let boxed = SHORT_HITBOX.offset(self.pos);
+                if touching_wall(world.map, world.solids, boxed, direction) {
+                    self.facing_right = !self.facing_right;
+                }
+            }
+            FlightPattern::Chase { .. } if delta.x != 0.0 => self.facing_right = delta.x > 0.0,
+            FlightPattern::Chase { .. } => {}
+        }
+        if overlapping(SHORT_HITBOX.offset(self.pos), ctx.player.hitbox()) {
+            ctx.player.damage(self.damage, self.pos);
         }
-        forces.x -= self.velocity.x
-            * if self.on_ground {
-                GROUND_FRICTION
+    }
+    fn hitbox(&self) -> Option<Rect> {
+        Some(SHORT_HITBOX.offset(self.pos))
+    }
+    fn velocity(&self) -> Vec2 {
+        self.pos - self.last_pos
+    }
+    fn draw(&self, ctx: &mut ScreenUpdateContext, alpha: f32) {
+        let pos = self.last_pos.lerp(self.pos, alpha);
+        draw_animation(
+            ctx,
+            self.animation,
+            self.anim_frame,
+            pos,
+            !self.facing_right,
+        );
+    }
+}
+
+/// Longest a projectile flies for, in ticks, in case it never hits anything
+const PROJECTILE_LIFETIME: u32 = 300;
+/// Width and height of a projectile
+const PROJECTILE_SIZE: f32 = 2.0;
+
+/// A shot fired by a [Turret], which flies straight until it hits something.
+struct Projectile {
+    /// Centre of the shot
+    pos: Vec2,
+    last_pos: Vec2,
+    velocity: Vec2,
+    ticks: u32,
+}
+impl Projectile {
+    fn hitbox(&self) -> Rect {
+        Rect::new(
+            self.pos.x - PROJECTILE_SIZE / 2.0,
+            self.pos.y - PROJECTILE_SIZE / 2.0,
+            PROJECTILE_SIZE,
+            PROJECTILE_SIZE,
+        )
+    }
+}
+
+/// A stationary enemy that shoots at the player on a timer, whenever it can see them.
+pub struct Turret {
+    /// Top left corner
+    pub pos: Vec2,
+    pub anim_frame: u32,
+    pub animation: AnimationID,
+    /// Ticks between shots
+    pub interval: u32,
+    /// How close the player has to be to get shot at, in pixels
+    pub range: f32,
+    /// Pixels its shots move per tick
+    pub projectile_speed: f32,
+    pub damage: f32,
+    /// Ticks left until it can shoot again
+    cooldown: u32,
+    projectiles: Vec<Projectile>,
+}
+impl Turret {
+    pub fn new(
+        pos: Vec2,
+        animation: AnimationID,
+        interval: u32,
+        range: f32,
+        projectile_speed: f32,
+        damage: f32,
+    ) -> Self {
+        Self {
+            pos,
+            anim_frame: 0,
+            animation,
+            interval,
+            range,
+            projectile_speed,
+            damage,
+            cooldown: interval,
+            projectiles: Vec::new(),
+        }
+    }
+}
+impl NonPlayerEntity for Turret {
+    fn update(&mut self, world: &World, ctx: &mut ScreenUpdateContext) {
+        self.anim_frame += 1000 / 60;
+        let player = &mut *ctx.player;
+        self.projectiles.retain_mut(|projectile| {
+            projectile.last_pos = projectile.pos;
+            projectile.pos += projectile.velocity;
+            projectile.ticks += 1;
+            if overlapping(projectile.hitbox(), player.hitbox()) {
+                player.damage(self.damage, projectile.pos);
+                return false;
+            }
+            !point_blocked(world, projectile.pos) && projectile.ticks < PROJECTILE_LIFETIME
+        });
+
+        self.cooldown = self.cooldown.saturating_sub(1);
+        let muzzle = SHORT_HITBOX.offset(self.pos).center();
+        let target = player.hitbox().center();
+        // waits for a clear shot once it's ready, instead of wasting it
+        if self.cooldown == 0
+            && !player.is_dead()
+            && muzzle.distance(target) <= self.range
+            && line_of_sight(world, muzzle, target)
+        {
+            self.projectiles.push(Projectile {
+                pos: muzzle,
+                last_pos: muzzle,
+                velocity: (target - muzzle).normalize_or_zero() * self.projectile_speed,
+                ticks: 0,
+            });
+            self.cooldown = self.interval;
+        }
+    }
+    fn hitbox(&self) -> Option<Rect> {
+        Some(SHORT_HITBOX.offset(self.pos))
+    }
+    fn draw(&self, ctx: &mut ScreenUpdateContext, alpha: f32) {
+        draw_animation(ctx, self.animation, self.anim_frame, self.pos, false);
+        let layer = &mut ctx.render_layers.entities;
+        for projectile in &self.projectiles {
+            let pos = projectile.last_pos.lerp(projectile.pos, alpha) - PROJECTILE_SIZE / 2.0;
+            layer.calls.push(DrawCall::Rect(
+                pos.x.floor(),
+                pos.y.floor(),
+                PROJECTILE_SIZE,
+                PROJECTILE_SIZE,
+                ORANGE,
+            ));
+        }
+    }
+}
+
+/// A solid platform that moves along a path, carrying anything standing on it.
+pub struct MovingPlatform {
+    /// Top left corner
+    pub pos: Vec2,
+    pub last_pos: Vec2,
+    pub size: Vec2,
+    /// Points the top left corner visits in order, in world pixels
+    pub path: Vec<Vec2>,
+    /// Whether the path loops back round to its start, instead of being followed back
+    /// and forth
+    pub looped: bool,
+    /// Pixels moved per tick
+    pub speed: f32,
+    /// Index in `path` of the point being moved towards
+    target: usize,
+    /// Whether the path is being followed backwards
+    reversing: bool,
+}
+impl MovingPlatform {
+    /// Panics if `path` is empty.
+    pub fn new(path: Vec<Vec2>, looped: bool, size: Vec2, speed: f32) -> Self {
+        Self {
+            pos: path[0],
+            last_pos: path[0],
+            size,
+            target: 1 % path.len(),
+            path,
+            looped,
+            speed,
+            reversing: false,
+        }
+    }
+    /// Picks the next point on the path to move towards.
+    fn advance(&mut self) {
+        let last = self.path.len() - 1;
+        if self.looped {
+            self.target = (self.target + 1) % self.path.len();
+        } else if self.reversing {
+            if self.target == 0 {
+                self.reversing = false;
+                self.target = 1.min(last);
             } else {
-                AIR_DRAG
-            };
+                self.target -= 1;
+            }
+        } else if self.target == last {
+            self.reversing = true;
+            self.target = last.saturating_sub(1);
+        } else {
+            self.target += 1;
+        }
+    }
+}
+impl NonPlayerEntity for MovingPlatform {
+    fn update_solid(&mut self) -> Option<Solid> {
+        self.last_pos = self.pos;
+        let mut remaining = self.speed;
+        // at most one lap, so a path of identical points can't loop forever
+        for _ in 0..self.path.len() {
+            let to_target = self.path[self.target] - self.pos;
+            let distance = to_target.length();
+            if distance > remaining {
+                self.pos += to_target / distance * remaining;
+                break;
+            }
+            self.pos = self.path[self.target];
+            remaining -= distance;
+            self.advance();
+        }
+        Some(Solid {
+            rect: Rect::new(self.pos.x, self.pos.y, self.size.x, self.size.y),
+            delta: self.pos - self.last_pos,
+        })
+    }
+    fn hitbox(&self) -> Option<Rect> {
+        Some(Rect::new(self.pos.x, self.pos.y, self.size.x, self.size.y))
+    }
+    fn velocity(&self) -> Vec2 {
+        self.pos - self.last_pos
+    }
+    fn draw(&self, ctx: &mut ScreenUpdateContext, alpha: f32) {
+        let pos = self.last_pos.lerp(self.pos, alpha).floor();
+        ctx.render_layers.entities.calls.push(DrawCall::Rect(
+            pos.x,
+            pos.y,
+            self.size.x,
+            self.size.y,
+            LIGHTGRAY,
+        ));
+    }
+}
 
-        let result =
-            update_physics_entity(&mut self.pos, &mut forces, &mut self.velocity, true, map);
-        self.on_ground = result.0;
+/// Hitbox of a standing humanoid, relative to its position.
+pub const TALL_HITBOX: Rect = Rect::new(0.0, -8.0, 8.0, 16.0);
+/// Hitbox of a crouching or sliding humanoid, relative to its position.
+pub const SHORT_HITBOX: Rect = Rect::new(0.0, 0.0, 8.0, 8.0);
+
+/// What an entity touched while moving.
+#[derive(Clone, Copy, Default)]
+pub struct Contacts {
+    /// Normals of the surfaces hit on each axis, or zero where nothing was hit.
+    ///
+    /// A `y` of `-1.0` means the entity landed on the ground.
+    pub normal: Vec2,
+    /// Normal of the ground stood on, which is tilted on slopes, or zero in the air
+    pub ground_normal: Vec2,
+    /// Whether the tiles directly above the hitbox are solid
+    pub head_covered: bool,
+    /// Whether a wall is right up against the left of the hitbox
+    pub wall_left: bool,
+    /// Whether a wall is right up against the right of the hitbox
+    pub wall_right: bool,
+    /// Whether a solid pushed the entity into a wall, or carried it into a ceiling
+    pub crushed: bool,
+}
+
+/// What entities move through and collide with during a tick.
+pub struct World<'a> {
+    pub map: &'a Map,
+    /// Every entity's solid, already moved this tick
+    pub solids: &'a [Solid],
+    pub config: &'a MovementConfig,
+    /// Where humanoids can go on `map`, if anything on it navigates
+    pub nav: Option<&'a NavGraph>,
+}
+#[cfg(test)]
+impl<'a> World<'a> {
+    /// `map` and `solids` with the built-in movement config, and nothing to navigate.
+    pub fn built_in(map: &'a Map, solids: &'a [Solid]) -> Self {
+        static CONFIG: std::sync::LazyLock<MovementConfig> =
+            std::sync::LazyLock::new(MovementConfig::built_in);
+        Self {
+            map,
+            solids,
+            config: &CONFIG,
+            nav: None,
+        }
     }
 }
 
+/// Something entities collide with besides the map, like a moving platform.
+#[derive(Clone, Copy)]
+pub struct Solid {
+    pub rect: Rect,
+    /// How far it moved this tick
+    pub delta: Vec2,
+}
+
+/// Whether two spans overlap by more than just touching.
+fn spans_overlap(a: (f32, f32), b: (f32, f32)) -> bool {
+    a.0 < b.1 && a.1 > b.0
+}
+/// Whether two rects overlap by more than just touching.
+fn overlapping(a: Rect, b: Rect) -> bool {
+    spans_overlap((a.left(), a.right()), (b.left(), b.right()))
+        && spans_overlap((a.top(), a.bottom()), (b.top(), b.bottom()))
+}
+impl Contacts {
+    pub fn on_ground(&self) -> bool {
+        self.normal.y < 0.0
+    }
+}
+
+/// Whether any tile in the given column, over the rows `rows`, is solid.
+fn column_solid(map: &Map, x: i32, rows: (i32, i32)) -> bool {
+    (rows.0..=rows.1).any(|y| map.collision_tile(x, y) == CollisionTile::Solid)
+}
+/// Whether any tile in the given row, over the columns `columns`, is solid.
+///
+/// With `platforms`, one-way platforms count as solid too.
+fn row_solid(map: &Map, y: i32, columns: (i32, i32), platforms: bool) -> bool {
+    (columns.0..=columns.1).any(|x| match map.collision_tile(x, y) {
+        CollisionTile::Solid => true,
+        CollisionTile::Platform => platforms,
+        _ => false,
+    })
+}
+
+/// The tiles a span from `start` to `end` overlaps, inclusive.
+fn tile_span(start: f32, end: f32) -> (i32, i32) {
+    ((start / 8.0).floor() as i32, (end / 8.0).ceil() as i32 - 1)
+}
+
+/// Moves a span along one axis by `delta`, stopping at the first blocked tile in the way.
+///
+/// Returns the distance actually moved, and whether it was blocked.
+fn sweep(start: f32, end: f32, delta: f32, blocked: impl Fn(i32) -> bool) -> (f32, bool) {
+    if delta > 0.0 {
+        // tiles newly entered by the leading edge, nearest first
+        let first = (end / 8.0).ceil() as i32;
+        let last = ((end + delta) / 8.0).ceil() as i32 - 1;
+        for tile in first..=last {
+            if blocked(tile) {
+                return (tile as f32 * 8.0 - end, true);
+            }
+        }
+    } else if delta < 0.0 {
+        let first = (start / 8.0).floor() as i32 - 1;
+        let last = ((start + delta) / 8.0).floor() as i32;
+        for tile in (last..=first).rev() {
+            if blocked(tile) {
+                return ((tile + 1) as f32 * 8.0 - start, true);
+            }
+        }
+    }
+    (delta, false)
+}
+
+/// Horizontal spans of the solids level with a hitbox.
+fn solids_beside(solids: &[Solid], boxed: Rect) -> impl Iterator<Item = (f32, f32)> + '_ {
+    solids
+        .iter()
+        .filter(move |solid| {
+            spans_overlap(
+                (boxed.top(), boxed.bottom()),
+                (solid.rect.top(), solid.rect.bottom()),
+            )
+        })
+        .map(|solid| (solid.rect.left(), solid.rect.right()))
+}
+/// Vertical spans of the solids directly above or below a hitbox.
+fn solids_above_below(solids: &[Solid], boxed: Rect) -> impl Iterator<Item = (f32, f32)> + '_ {
+    solids
+        .iter()
+        .filter(move |solid| {
+            spans_overlap(
+                (boxed.left(), boxed.right()),
+                (solid.rect.left(), solid.rect.right()),
+            )
+        })
+        .map(|solid| (solid.rect.top(), solid.rect.bottom()))
+}
+/// Moves a span along one axis by `delta`, stopping at the first of `obstacles` in the way,
+/// which are spans along the same axis.
+///
+/// Returns the distance actually moved, and whether it was blocked.
+fn sweep_solids(
+    start: f32,
+    end: f32,
+    delta: f32,
+    obstacles: impl Iterator<Item = (f32, f32)>,
+) -> (f32, bool) {
+    let mut moved = delta;
+    let mut hit = false;
+    for (obstacle_start, obstacle_end) in obstacles {
+        if delta > 0.0 && obstacle_start >= end - 0.01 && obstacle_start - end < moved {
+            moved = (obstacle_start - end).max(0.0);
+            hit = true;
+        } else if delta < 0.0 && obstacle_end <= start + 0.01 && obstacle_end - start > moved {
+            moved = (obstacle_end - start).min(0.0);
+            hit = true;
+        }
+    }
+    (moved, hit)
+}
+/// Moves a hitbox by `delta` against the map's solid tiles, as when carried or pushed
+/// by a solid.
+///
+/// Returns whether it was blocked.
+fn shove(pos: &mut Vec2, hitbox: Rect, delta: Vec2, map: &Map) -> bool {
+    let boxed = hitbox.offset(*pos);
+    let columns = tile_span(boxed.left(), boxed.right());
+    let (moved_y, hit_y) = sweep(boxed.top(), boxed.bottom(), delta.y, |y| {
+        row_solid(map, y, columns, false)
+    });
+    pos.y += moved_y;
+    let boxed = hitbox.offset(*pos);
+    let rows = tile_span(boxed.top(), boxed.bottom());
+    let (moved_x, hit_x) = sweep(boxed.left(), boxed.right(), delta.x, |x| {
+        column_solid(map, x, rows)
+    });
+    pos.x += moved_x;
+    hit_x || hit_y
+}
+/// Moves a hitbox that ignores gravity by `delta`, against the map's solid tiles and
+/// every solid.
+fn fly(pos: &mut Vec2, hitbox: Rect, delta: Vec2, world: &World) {
+    // whichever of the map and the solids is in the way first
+    let nearest = |(a, _): (f32, bool), (b, _): (f32, bool)| if a.abs() < b.abs() { a } else { b };
+    let boxed = hitbox.offset(*pos);
+    let columns = tile_span(boxed.left(), boxed.right());
+    pos.y += nearest(
+        sweep(boxed.top(), boxed.bottom(), delta.y, |y| {
+            row_solid(world.map, y, columns, false)
+        }),
+        sweep_solids(
+            boxed.top(),
+            boxed.bottom(),
+            delta.y,
+            solids_above_below(world.solids, boxed),
+        ),
+    );
+    let boxed = hitbox.offset(*pos);
+    let rows = tile_span(boxed.top(), boxed.bottom());
+    pos.x += nearest(
+        sweep(boxed.left(), boxed.right(), delta.x, |x| {
+            column_solid(world.map, x, rows)
+        }),
+        sweep_solids(
+            boxed.left(),
+            boxed.right(),
+            delta.x,
+            solids_beside(world.solids, boxed),
+        ),
+    );
+}
+/// Whether a wall is right up against one side of a hitbox, to the right if `direction`
+/// is positive and the left otherwise.
+fn touching_wall(map: &Map, solids: &[Solid], boxed: Rect, direction: f32) -> bool {
+    let probe = 0.01 * direction.signum();
+    let rows = tile_span(boxed.top(), boxed.bottom());
+    let (_, hit) = sweep(boxed.left(), boxed.right(), probe, |x| {
+        column_solid(map, x, rows)
+    });
+    let (_, hit_solid) = sweep_solids(
+        boxed.left(),
+        boxed.right(),
+        probe,
+        solids_beside(solids, boxed),
+    );
+    hit || hit_solid
+}
+/// Whether a point is inside a solid, or under the floor of a tile, which for a ramp is
+/// its surface and for anything else is the whole tile.
+fn point_blocked(world: &World, point: Vec2) -> bool {
+    let (x, y) = (
+        (point.x / 8.0).floor() as i32,
+        (point.y / 8.0).floor() as i32,
+    );
+    let in_tile = world
+        .map
+        .collision_tile(x, y)
+        .floor_height(point.x - x as f32 * 8.0)
+        .is_some_and(|height| point.y >= (y + 1) as f32 * 8.0 - height);
+    in_tile || world.solids.iter().any(|solid| solid.rect.contains(point))
+}
+/// Whether a straight line between two points is clear of tiles and solids.
+fn line_of_sight(world: &World, from: Vec2, to: Vec2) -> bool {
+    let steps = from.distance(to).ceil().max(1.0) as usize;
+    (0..=steps).all(|i| !point_blocked(world, from.lerp(to, i as f32 / steps as f32)))
+}
+/// How far to push a hitbox so a solid moving by `delta` no longer overlaps it, along
+/// whichever axis of the solid's movement needs the shorter push.
+fn push_out(boxed: Rect, solid: &Solid) -> Vec2 {
+    let x = if solid.delta.x > 0.0 {
+        Some(solid.rect.right() - boxed.left())
+    } else if solid.delta.x < 0.0 {
+        Some(solid.rect.left() - boxed.right())
+    } else {
+        None
+    };
+    let y = if solid.delta.y > 0.0 {
+        Some(solid.rect.bottom() - boxed.top())
+    } else if solid.delta.y < 0.0 {
+        Some(solid.rect.top() - boxed.bottom())
+    } else {
+        None
+    };
+    match (x, y) {
+        (Some(x), Some(y)) if x.abs() < y.abs() => Vec2::new(x, 0.0),
+        (_, Some(y)) => Vec2::new(0.0, y),
+        (Some(x), None) => Vec2::new(x, 0.0),
+        (None, None) => Vec2::ZERO,
+    }
+}
+
+/// The tile the bottom centre of a hitbox stands in, as the column and row.
+fn foot_tile(foot: Vec2) -> (i32, i32) {
+    (
+        (foot.x / 8.0).floor() as i32,
+        ((foot.y - 0.01) / 8.0).floor() as i32,
+    )
+}
+/// Whether there's a slope around the bottom centre of a hitbox.
+fn near_slope(map: &Map, foot: Vec2) -> bool {
+    let (x, y) = foot_tile(foot);
+    (y - 1..=y + 1).any(|y| matches!(map.collision_tile(x, y), CollisionTile::Slope(..)))
+}
+/// Finds the highest floor below the bottom centre of a hitbox, between `up` pixels
+/// above and `down` pixels below it.
+///
+/// Returns the floor's y and normal.
+fn find_floor(
+    map: &Map,
+    foot: Vec2,
+    up: f32,
+    down: f32,
+    drop_through: bool,
+) -> Option<(f32, Vec2)> {
+    let (x, y) = foot_tile(foot);
+    (y - 1..=y + 1).find_map(|y| {
+        let tile = map.collision_tile(x, y);
+        if drop_through && tile == CollisionTile::Platform {
+            return None;
+        }
+        let height = tile.floor_height(foot.x - x as f32 * 8.0)?;
+        let floor = (y + 1) as f32 * 8.0 - height;
+        if floor < foot.y - up || floor > foot.y + down {
+            return None;
+        }
+        let normal = match tile {
+            CollisionTile::Slope(left, right) => Vec2::new(left - right, -8.0).normalize(),
+            _ => Vec2::new(0.0, -1.0),
+        };
+        Some((floor, normal))
+    })
+}
+/// Finds the first ramp surface the bottom centre of a hitbox crosses falling `delta`
+/// pixels.
+///
+/// Returns the distance to the surface, and its normal.
+fn sweep_slopes(map: &Map, foot: Vec2, delta: f32) -> Option<(f32, Vec2)> {
+    let (x, first) = foot_tile(foot);
+    let last = ((foot.y + delta) / 8.0).floor() as i32;
+    (first..=last).find_map(|y| {
+        let tile = map.collision_tile(x, y);
+        let CollisionTile::Slope(left, right) = tile else {
+            return None;
+        };
+        let floor = (y + 1) as f32 * 8.0 - tile.floor_height(foot.x - x as f32 * 8.0)?;
+        let distance = floor - foot.y;
+        (-0.01..=delta)
+            .contains(&distance)
+            .then(|| (distance.max(0.0), Vec2::new(left - right, -8.0).normalize()))
+    })
+}
+
+/// Applies gravity and `forces` to an entity, then moves it by its velocity against the
+/// map's collision layer.
+///
+/// Movement is swept one axis at a time, vertical first, so no speed can pass through
+/// a tile. Falls stop on a ramp's surface, but slopes don't block any other movement.
+/// Instead, entities near them are kept on the floor afterwards, so they walk up ramps
+/// and don't fly off them going down.
+///
+/// One-way platforms only stop entities falling onto them from above, and not even
+/// then with `drop_through`.
+///
+/// `solids` have already moved this tick. Entities standing on one are carried along
+/// with it, and ones it moved into are pushed out of the way, or crushed if there's
+/// no room.
 pub fn update_physics_entity(
     pos: &mut Vec2,
     forces: &mut Vec2,
     velocity: &mut Vec2,
-    tall: bool,
-    map: &Map,
-) -> (bool, bool) {
-    forces.y += GRAVITY;
+    hitbox: Rect,
+    drop_through: bool,
+    world: &World,
+) -> Contacts {
+    let World {
+        map,
+        solids,
+        config,
+        ..
+    } = *world;
+    forces.y += config.gravity;
 
     *velocity += *forces;
 
-    let mut new = *pos + *velocity;
-
-    let tile_x = pos.x / 8.0;
-    let tile_y = pos.y / 8.0;
+    let mut contacts = Contacts::default();
 
-    let mut tiles_y = vec![
-        (tile_x.trunc(), ceil_g(new.y / 8.0)),
-        (ceil_g(tile_x), ceil_g(new.y / 8.0)),
-        (tile_x.trunc(), (new.y / 8.0).trunc()),
-        (ceil_g(tile_x), (new.y / 8.0).trunc()),
-    ];
-    if tall {
-        tiles_y.push((tile_x.trunc(), (new.y / 8.0).trunc() - 1.0));
-        tiles_y.push((ceil_g(tile_x), (new.y / 8.0).trunc() - 1.0));
+    let boxed = hitbox.offset(*pos);
+    let ridden = solids.iter().find(|solid| {
+        let last = solid.rect.offset(-solid.delta);
+        (boxed.bottom() - last.top()).abs() < 0.01
+            && spans_overlap((boxed.left(), boxed.right()), (last.left(), last.right()))
+    });
+    if let Some(solid) = ridden {
+        if velocity.y < 0.0 {
+            // jumping off keeps the platform's momentum
+            velocity.x += solid.delta.x;
+        } else {
+            shove(pos, hitbox, solid.delta, map);
+        }
     }
+    for solid in solids {
+        let boxed = hitbox.offset(*pos);
+        if overlapping(boxed, solid.rect) {
+            let push = push_out(boxed, solid);
+            contacts.crushed |= shove(pos, hitbox, push, map);
+        }
+    }
+    let foot = |pos: Vec2| {
+        let boxed = hitbox.offset(pos);
+        Vec2::new(boxed.center().x, boxed.bottom())
+    };
+    let was_near_slope = near_slope(map, foot(*pos));
 
-    let mut on_ground = false;
-    for (tx, ty) in tiles_y {
-        let tile = map.get_collision_tile(tx as _, ty as _);
-        if tile != 0 {
-            let c = if velocity.y < 0.0 {
-                tile_y.floor() * 8.0
-            } else {
-                on_ground = true;
-                tile_y.ceil() * 8.0
+    let boxed = hitbox.offset(*pos);
+    let columns = tile_span(boxed.left(), boxed.right());
+    // rows are only swept once entered, so platforms are always landed on from above
+    let platforms = velocity.y > 0.0 && !drop_through;
+    let (mut moved, mut hit_map) = sweep(boxed.top(), boxed.bottom(), velocity.y, |y| {
+        row_solid(map, y, columns, platforms)
+    });
+    let mut slope_normal = None;
+    if velocity.y > 0.0
+        && let Some((distance, normal)) = sweep_slopes(map, foot(*pos), moved)
+    {
+        moved = distance;
+        hit_map = false;
+        slope_normal = Some(normal);
+    }
+    let (moved, hit_solid) = sweep_solids(
+        boxed.top(),
+        boxed.bottom(),
+        moved,
+        solids_above_below(solids, boxed),
+    );
+    let hit_slope = slope_normal.is_some() && !hit_solid;
+    let hit = hit_map || hit_slope || hit_solid;
+    pos.y += moved;
+    // tile edges and hitboxes are on whole pixels, so stopping at the map lands exactly
+    // on the edge, rather than a rounding error away from it
+    if hit_map && !hit_solid {
+        pos.y = pos.y.round();
+    }
+    if hit {
+        contacts.normal.y = -velocity.y.signum();
+        if velocity.y > 0.0 {
+            contacts.ground_normal = match slope_normal {
+                Some(normal) if hit_slope => normal,
+                _ => Vec2::new(0.0, -1.0),
             };
-            new.y = c;
-            velocity.y = 0.0;
-            break;
         }
-    }
-    let mut tiles_x = vec![
-        ((new.x / 8.0).trunc(), ceil_g(new.y / 8.0)),
-        (ceil_g(new.x / 8.0), ceil_g(new.y / 8.0)),
-        (ceil_g(new.x / 8.0), (new.y / 8.0).trunc()),
-        ((new.x / 8.0).trunc(), (new.y / 8.0).trunc()),
-    ];
-    if tall {
-        tiles_x.push(((new.x / 8.0).trunc(), (new.y / 8.0).trunc() - 1.0));
-        tiles_x.push((ceil_g(new.x / 8.0), (new.y / 8.0).trunc() - 1.0));
+        velocity.y = 0.0;
     }
 
-    for (tx, ty) in tiles_x {
-        let tile = if tx >= 0.0 {
-            map.get_collision_tile(tx as _, ty as _)
+    // on slopes, the floor ahead may be a little higher, which the step up handles
+    let boxed = hitbox.offset(*pos);
+    let bottom = boxed.bottom()
+        - if was_near_slope {
+            config.step_height
         } else {
-            1
+            0.0
         };
-        if tile != 0 {
-            let c = if velocity.x < 0.0 {
-                tile_x.floor() * 8.0
-            } else {
-                tile_x.ceil() * 8.0
+    let rows = tile_span(boxed.top(), bottom);
+    let (moved, hit_map) = sweep(boxed.left(), boxed.right(), velocity.x, |x| {
+        column_solid(map, x, rows)
+    });
+    let (moved, hit_solid) = sweep_solids(
+        boxed.left(),
+        boxed.right(),
+        moved,
+        solids_beside(solids, boxed),
+    );
+    let hit = hit_map || hit_solid;
+    pos.x += moved;
+    if hit_map && !hit_solid {
+        pos.x = pos.x.round();
+    }
+    if hit {
+        contacts.normal.x = -velocity.x.signum();
+        velocity.x = 0.0;
+    }
+
+    if velocity.y >= 0.0
+        && (was_near_slope || near_slope(map, foot(*pos)))
+        && let Some((floor, normal)) = find_floor(
+            map,
+            foot(*pos),
+            config.step_height,
+            velocity.x.abs() + 1.0,
+            drop_through,
+        )
+    {
+        pos.y += floor - foot(*pos).y;
+        velocity.y = 0.0;
+        contacts.normal.y = -1.0;
+        contacts.ground_normal = normal;
+    }
+
+    let boxed = hitbox.offset(*pos);
+    let head_row = (boxed.top() / 8.0).ceil() as i32 - 1;
+    let (left, right) = tile_span(boxed.left(), boxed.right());
+    contacts.head_covered = (left..=right).any(|x| {
+        !matches!(
+            map.collision_tile(x, head_row),
+            CollisionTile::Empty | CollisionTile::Platform
+        )
+    }) || solids.iter().any(|solid| {
+        overlapping(
+            Rect::new(boxed.left(), boxed.top() - 8.0, boxed.w, 8.0),
+            solid.rect,
+        )
+    });
+
+    contacts.wall_left = touching_wall(map, solids, boxed, -1.0);
+    contacts.wall_right = touching_wall(map, solids, boxed, 1.0);
+
+    if velocity.x.abs() <= config.stop_speed {
+        velocity.x = 0.0;
+    }
+
+    contacts
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::{graphics::RenderLayers, input::Input, player::Player, tmx::Properties};
+
+    /// Steps a standing humanoid's hitbox with no forces but gravity for `ticks`,
+    /// returning the contacts from the last tick.
+    fn fall(map: &Map, pos: &mut Vec2, velocity: &mut Vec2, ticks: usize) -> Contacts {
+        fall_through(map, pos, velocity, ticks, false)
+    }
+    /// Like [fall], but dropping through one-way platforms with `drop_through`.
+    fn fall_through(
+        map: &Map,
+        pos: &mut Vec2,
+        velocity: &mut Vec2,
+        ticks: usize,
+        drop_through: bool,
+    ) -> Contacts {
+        let world = World::built_in(map, &[]);
+        let mut contacts = Contacts::default();
+        for _ in 0..ticks {
+            let mut forces = Vec2::ZERO;
+            contacts = update_physics_entity(
+                pos,
+                &mut forces,
+                velocity,
+                TALL_HITBOX,
+                drop_through,
+                &world,
+            );
+        }
+        contacts
+    }
+    /// Walks a standing humanoid's hitbox along the ground, pushed by `force` each tick
+    /// against ground friction, calling `each` with its position and contacts every tick.
+    fn walk(
+        map: &Map,
+        pos: &mut Vec2,
+        force: f32,
+        ticks: usize,
+        mut each: impl FnMut(Vec2, Contacts),
+    ) {
+        let world = World::built_in(map, &[]);
+        let mut velocity = Vec2::ZERO;
+        for _ in 0..ticks {
+            let mut forces = Vec2::new(force - velocity.x * world.config.ground_friction, 0.0);
+            let contacts =
+                update_physics_entity(pos, &mut forces, &mut velocity, TALL_HITBOX, false, &world);
+            each(*pos, contacts);
+        }
+    }
+    /// Moves `platform`, then steps a standing humanoid's hitbox with no forces but
+    /// gravity against it, for `ticks`. Calls `each` with the platform's solid, and the
+    /// hitbox's position and contacts, every tick.
+    fn ride(
+        map: &Map,
+        platform: &mut MovingPlatform,
+        pos: &mut Vec2,
+        ticks: usize,
+        mut each: impl FnMut(Solid, Vec2, Contacts),
+    ) {
+        let mut velocity = Vec2::ZERO;
+        for _ in 0..ticks {
+            let solid = platform.update_solid().unwrap();
+            let solids = [solid];
+            let world = World::built_in(map, &solids);
+            let mut forces = Vec2::ZERO;
+            let contacts =
+                update_physics_entity(pos, &mut forces, &mut velocity, TALL_HITBOX, false, &world);
+            each(solid, *pos, contacts);
+        }
+    }
+    /// Updates `entity` for `ticks` among `solids`, with the player standing at
+    /// `player_pos`, returning the player afterwards.
+    fn run(
+        entity: &mut dyn NonPlayerEntity,
+        map: &Map,
+        solids: &[Solid],
+        player_pos: Vec2,
+        ticks: usize,
+        mut each: impl FnMut(&dyn NonPlayerEntity),
+    ) -> Player {
+        let world = World::built_in(map, solids);
+        let nav = entity
+            .navigates()
+            .then(|| NavGraph::build(map, &humanoid_arc(world.config)));
+        let world = World {
+            nav: nav.as_ref(),
+            ..world
+        };
+        let mut player = Player::new();
+        player.pos = player_pos;
+        let mut render_layers = RenderLayers::null();
+        for _ in 0..ticks {
+            let mut ctx = ScreenUpdateContext {
+                player: &mut player,
+                render_layers: &mut render_layers,
+                config: world.config,
+                debug_overlay: false,
+                input: Input::default(),
             };
-            new.x = c;
-            velocity.x = 0.0;
-            break;
+            entity.update(&world, &mut ctx);
+            each(entity);
         }
+        player
+    }
+
+    const FLOOR: &str = "
+        ........
+        ........
+        ........
+        ........
+        ########
+    ";
+
+    #[test]
+    fn lands_exactly_on_the_floor() {
+        let map = Map::from_ascii(FLOOR);
+        let mut pos = Vec2::new(20.0, 3.3);
+        let mut velocity = Vec2::ZERO;
+        let contacts = fall(&map, &mut pos, &mut velocity, 30);
+        assert!(contacts.on_ground());
+        assert_eq!(contacts.ground_normal, Vec2::new(0.0, -1.0));
+        // the hitbox's bottom is on top of row 4
+        assert_eq!(pos, Vec2::new(20.0, 24.0));
+        assert_eq!(velocity, Vec2::ZERO);
+
+        // and stays there
+        let contacts = fall(&map, &mut pos, &mut velocity, 10);
+        assert!(contacts.on_ground());
+        assert_eq!(pos.y, 24.0);
     }
 
-    // check if head covered
-    let m = if tall { 2.0 } else { 1.0 };
-    let tiles_head = [
-        ((new.x / 8.0).trunc(), ceil_g(new.y / 8.0) - m),
-        (ceil_g(new.x / 8.0), ceil_g(new.y / 8.0) - m),
-    ];
-    let mut head_covered = false;
-    for (tx, ty) in tiles_head {
-        let tile = map.get_collision_tile(tx as _, ty as _);
-        if tile != 0 {
-            head_covered = true;
+    #[test]
+    fn fast_falls_land_without_passing_through() {
+        let map = Map::from_ascii(FLOOR);
+        let mut pos = Vec2::new(20.0, 0.0);
+        let mut velocity = Vec2::new(0.0, 30.0);
+        let contacts = fall(&map, &mut pos, &mut velocity, 1);
+        assert!(contacts.on_ground());
+        assert_eq!(pos.y, 24.0);
+    }
+
+    #[test]
+    fn left_edge_of_map_is_a_wall() {
+        let map = Map::from_ascii(FLOOR);
+        let mut pos = Vec2::new(3.0, 24.0);
+        let mut velocity = Vec2::new(-5.0, 0.0);
+        let contacts = fall(&map, &mut pos, &mut velocity, 1);
+        assert_eq!(pos, Vec2::new(0.0, 24.0));
+        assert_eq!(contacts.normal.x, 1.0);
+        assert!(contacts.wall_left);
+        assert!(contacts.on_ground());
+        assert_eq!(velocity.x, 0.0);
+    }
+
+    #[test]
+    fn creeping_left_stops_at_zero() {
+        let map = Map::from_ascii(FLOOR);
+        let mut pos = Vec2::new(1.0, 24.0);
+        for _ in 0..10 {
+            let mut velocity = Vec2::new(-0.35, 0.0);
+            fall(&map, &mut pos, &mut velocity, 1);
+            assert!(pos.x >= 0.0, "went off the map to {}", pos.x);
         }
+        assert_eq!(pos.x, 0.0);
     }
 
-    if velocity.x.abs() <= 0.3 {
-        velocity.x = 0.0;
+    #[test]
+    fn head_covered_under_a_ceiling() {
+        let map = Map::from_ascii(
+            "
+            ........
+            ###.....
+            ........
+            ........
+            ########
+            ",
+        );
+        let mut pos = Vec2::new(4.0, 24.0);
+        let contacts = fall(&map, &mut pos, &mut Vec2::new(0.0, 0.0), 1);
+        assert!(contacts.head_covered);
+        let mut pos = Vec2::new(32.0, 24.0);
+        let contacts = fall(&map, &mut pos, &mut Vec2::new(0.0, 0.0), 1);
+        assert!(!contacts.head_covered);
+    }
+
+    #[test]
+    fn humanoids_jump_up_onto_ledges() {
+        let map = Map::from_ascii(
+            "
+            ........
+            ........
+            ........
+            .....###
+            .....###
+            ########
+            ",
+        );
+        let mut enemy = HumanoidEnemy::new(Vec2::new(8.0, 32.0), AnimationID::PlayerSprint, 0.4);
+        let mut reached = false;
+        let player = run(
+            &mut enemy,
+            &map,
+            &[],
+            Vec2::new(56.0, 16.0),
+            300,
+            |entity| {
+                let hitbox = entity.hitbox().unwrap();
+                reached |= hitbox.bottom() == 24.0 && hitbox.left() >= 40.0;
+            },
+        );
+        assert!(reached, "never got up the ledge");
+        assert!(
+            player.health < player.max_health,
+            "never reached the player"
+        );
+    }
+
+    #[test]
+    fn patrollers_turn_at_walls_and_ledges() {
+        let map = Map::from_ascii(
+            "
+            ............
+            ............
+            #...........
+            #...........
+            #######.....
+            ............
+            ............
+            ",
+        );
+        let mut patroller =
+            Patroller::new(Vec2::new(16.0, 24.0), AnimationID::TestBox, true, 0.5, 10.0);
+        let (mut left, mut right) = (f32::MAX, f32::MIN);
+        run(
+            &mut patroller,
+            &map,
+            &[],
+            Vec2::new(0.0, 500.0),
+            300,
+            |entity| {
+                let hitbox = entity.hitbox().unwrap();
+                assert_eq!(hitbox.bottom(), 32.0, "fell off the floor");
+                left = left.min(hitbox.left());
+                right = right.max(hitbox.right());
+            },
+        );
+        assert_eq!(left, 8.0);
+        // turns before its whole hitbox passes the ledge at x = 56
+        assert!((50.0..62.0).contains(&right), "{right}");
+    }
+
+    #[test]
+    fn sine_flyers_ignore_gravity() {
+        let map = Map::from_ascii(
+            "
+            #..........#
+            #..........#
+            #..........#
+            #..........#
+            #..........#
+            ",
+        );
+        let pattern = FlightPattern::Sine {
+            amplitude: 8.0,
+            period: 60.0,
+        };
+        let mut flyer = Flyer::new(
+            Vec2::new(16.0, 16.0),
+            AnimationID::TestBox,
+            pattern,
+            1.0,
+            10.0,
+        );
+        let (mut top, mut bottom) = (f32::MAX, f32::MIN);
+        run(
+            &mut flyer,
+            &map,
+            &[],
+            Vec2::new(0.0, 500.0),
+            300,
+            |entity| {
+                let hitbox = entity.hitbox().unwrap();
+                assert!(hitbox.left() >= 8.0 && hitbox.right() <= 88.0);
+                top = top.min(hitbox.top());
+                bottom = bottom.max(hitbox.top());
+            },
+        );
+        assert!(top < 9.0 && bottom > 23.0, "{top}..{bottom}");
     }
-    *pos = new;
 
-    (on_ground, head_covered)
+    #[test]
+    fn flyers_stop_at_solids() {
+        let map = Map::from_ascii(
+            "
+            #...........
+            #...........
+            #...........
+            #...........
+            #...........
+            ",
+        );
+        let wall = Solid {
+            rect: Rect::new(64.0, 0.0, 8.0, 40.0),
+            delta: Vec2::ZERO,
+        };
+        let pattern = FlightPattern::Chase { range: 128.0 };
+        let mut flyer = Flyer::new(
+            Vec2::new(16.0, 16.0),
+            AnimationID::TestBox,
+            pattern,
+            1.0,
+            10.0,
+        );
+        let mut right = f32::MIN;
+        run(
+            &mut flyer,
+            &map,
+            &[wall],
+            Vec2::new(80.0, 24.0),
+            120,
+            |entity| right = right.max(entity.hitbox().unwrap().right()),
+        );
+        assert_eq!(right, 64.0);
+    }
+
+    #[test]
+    fn turrets_need_line_of_sight() {
+        // a wall across the shot, made of `wall` besides the row it passes through
+        let map = |wall: char, across: char| {
+            Map::from_ascii(&format!(
+                "
+                ......{wall}.....
+                ......{wall}.....
+                ......{across}.....
+                ......{wall}.....
+                ############
+                "
+            ))
+        };
+        let solid = Solid {
+            rect: Rect::new(48.0, 0.0, 8.0, 32.0),
+            delta: Vec2::ZERO,
+        };
+        let player_pos = Vec2::new(80.0, 24.0);
+        for (map, solids, hit) in [
+            (map('#', '#'), &[][..], false),
+            (map('#', '/'), &[][..], false),
+            (map('-', '-'), &[][..], false),
+            (map('.', '.'), &[solid][..], false),
+            (map('.', '.'), &[][..], true),
+        ] {
+            let mut turret = Turret::new(
+                Vec2::new(8.0, 16.0),
+                AnimationID::TestBox,
+                30,
+                128.0,
+                2.0,
+                10.0,
+            );
+            let player = run(&mut turret, &map, solids, player_pos, 120, |_| {});
+            assert_eq!(player.health < player.max_health, hit);
+        }
+    }
+
+    const HILL: &str = "
+        ..................
+        ..................
+        ..................
+        ......./##\\.......
+        ....../####\\......
+        ##################
+    ";
+
+    #[test]
+    fn walks_up_and_down_ramps() {
+        let map = Map::from_ascii(HILL);
+        for (start, force) in [(8.0, 0.5), (128.0, -0.5)] {
+            let mut pos = Vec2::new(start, 32.0);
+            let mut top = f32::MAX;
+            walk(&map, &mut pos, force, 60, |pos, contacts| {
+                assert!(contacts.on_ground(), "left the ground at {pos}");
+                top = top.min(pos.y);
+            });
+            // went over the top of the hill, and back down to the floor past it
+            assert_eq!(top, 16.0);
+            assert!((pos.x - start).abs() > 100.0, "stopped at {pos}");
+            assert_eq!(pos.y, 32.0);
+        }
+    }
+
+    #[test]
+    fn fast_falls_land_on_ramps() {
+        let map = Map::from_ascii(
+            "
+            ............
+            ............
+            ............
+            ............
+            ............
+            ..../\\......
+            ............
+            ............
+            ############
+            ",
+        );
+        for (x, ramp) in [(32.0, Vec2::new(-1.0, -1.0)), (40.0, Vec2::new(1.0, -1.0))] {
+            let mut pos = Vec2::new(x, 0.0);
+            let mut velocity = Vec2::new(0.0, 50.0);
+            let contacts = fall(&map, &mut pos, &mut velocity, 1);
+            assert!(contacts.on_ground());
+            assert_eq!(contacts.ground_normal, ramp.normalize());
+            // the foot is halfway along the ramp, 4 pixels up from the bottom of row 5
+            assert!((pos.y - 36.0).abs() < 0.01, "landed at {pos}");
+
+            // and stays on it
+            let contacts = fall(&map, &mut pos, &mut velocity, 10);
+            assert!(contacts.on_ground());
+            assert!((pos.y - 36.0).abs() < 0.01, "sank to {pos}");
+        }
+    }
+
+    const PLATFORM: &str = "
+        ..........
+        ..........
+        ..........
+        ..........
+        ....----..
+        ..........
+        ..........
+        ##########
+    ";
+
+    #[test]
+    fn lands_on_platforms_from_above() {
+        let map = Map::from_ascii(PLATFORM);
+        let mut pos = Vec2::new(36.0, 0.0);
+        let mut velocity = Vec2::ZERO;
+        let contacts = fall(&map, &mut pos, &mut velocity, 30);
+        assert!(contacts.on_ground());
+        assert_eq!(pos.y, 24.0);
+    }
+
+    #[test]
+    fn jumps_up_through_platforms() {
+        let map = Map::from_ascii(PLATFORM);
+        let mut pos = Vec2::new(36.0, 48.0);
+        let mut velocity = Vec2::new(0.0, -9.0);
+        let contacts = fall(&map, &mut pos, &mut velocity, 3);
+        assert!(!contacts.head_covered);
+        // the head is already past the platform, and hasn't been stopped by it
+        assert!(pos.y - 8.0 < 32.0, "stopped at {pos}");
+        assert!(velocity.y < 0.0);
+
+        // and it lands on top coming back down
+        let contacts = fall(&map, &mut pos, &mut velocity, 40);
+        assert!(contacts.on_ground());
+        assert_eq!(pos.y, 24.0);
+    }
+
+    #[test]
+    fn drops_through_platforms() {
+        let map = Map::from_ascii(PLATFORM);
+        let mut pos = Vec2::new(36.0, 24.0);
+        let mut velocity = Vec2::ZERO;
+        let contacts = fall_through(&map, &mut pos, &mut velocity, 30, true);
+        assert!(contacts.on_ground());
+        assert_eq!(pos.y, 48.0);
+
+        // but not without dropping through
+        let mut pos = Vec2::new(36.0, 24.0);
+        fall(&map, &mut pos, &mut velocity, 30);
+        assert_eq!(pos.y, 24.0);
+    }
+
+    #[test]
+    fn platforms_need_a_path() {
+        let object = Object {
+            id: 7,
+            name: String::new(),
+            class: "MovingPlatform".to_string(),
+            x: 16.0,
+            y: 16.0,
+            width: 0.0,
+            height: 0.0,
+            points: Vec::new(),
+            closed: false,
+            properties: Properties::default(),
+        };
+        assert!(matches!(
+            from_object(&object),
+            Err(TmxErrorKind::InvalidProperty { object: 7, name, .. }) if name == "points"
+        ));
+    }
+
+    #[test]
+    fn sine_flyers_need_a_positive_period() {
+        for period in ["0", "-60"] {
+            let object = Object {
+                id: 3,
+                name: String::new(),
+                class: "Flyer".to_string(),
+                x: 16.0,
+                y: 16.0,
+                width: 0.0,
+                height: 0.0,
+                points: Vec::new(),
+                closed: false,
+                properties: Properties(HashMap::from([("period".to_string(), period.to_string())])),
+            };
+            assert!(matches!(
+                from_object(&object),
+                Err(TmxErrorKind::InvalidProperty { object: 3, name, .. }) if name == "period"
+            ));
+        }
+    }
+
+    #[test]
+    fn platforms_carry_riders() {
+        let map = Map::from_ascii(
+            "
+            ............
+            ............
+            ............
+            ............
+            ............
+            ",
+        );
+        let path = vec![Vec2::new(16.0, 32.0), Vec2::new(64.0, 32.0)];
+        let mut platform = MovingPlatform::new(path, false, Vec2::new(24.0, 8.0), 1.0);
+        let mut pos = Vec2::new(20.0, 24.0);
+        ride(&map, &mut platform, &mut pos, 20, |solid, pos, contacts| {
+            assert!(contacts.on_ground(), "fell off at {pos}");
+            assert_eq!(pos, Vec2::new(solid.rect.x + 4.0, 24.0));
+        });
+        assert_eq!(pos.x, 40.0);
+    }
+
+    #[test]
+    fn platforms_push_and_crush_against_walls() {
+        let map = Map::from_ascii(
+            "
+            ..........#
+            ..........#
+            ..........#
+            ###########
+            ",
+        );
+        // level with the body of anything standing on the floor
+        let path = vec![Vec2::new(0.0, 12.0), Vec2::new(96.0, 12.0)];
+        let mut platform = MovingPlatform::new(path, false, Vec2::new(16.0, 8.0), 1.0);
+        let mut pos = Vec2::new(40.0, 16.0);
+        let mut crushed = false;
+        ride(&map, &mut platform, &mut pos, 80, |solid, pos, contacts| {
+            if crushed {
+                return;
+            }
+            crushed = contacts.crushed;
+            if solid.rect.right() > 40.0 && !crushed {
+                // pushed along in front of it, not overlapping it
+                assert_eq!(pos.x, solid.rect.right());
+            }
+        });
+        assert!(crushed);
+        // stopped against the wall
+        assert_eq!(pos.x, 72.0);
+    }
 }
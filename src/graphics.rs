@@ -7,6 +7,12 @@ use macroquad::prelude::*;
 pub enum DrawCall {
     Animation(AnimationID, u32, f32, f32, Option<DrawTextureParams>),
     Rect(f32, f32, f32, f32, Color),
+    /// Outline of a rect, one pixel thick
+    RectLines(f32, f32, f32, f32, Color),
+    /// Line between two points, one pixel thick
+    Line(f32, f32, f32, f32, Color),
+    /// Text with its baseline starting at the given point
+    Text(String, f32, f32, Color),
     Tileset(f32, f32, f32, f32),
     Clear(Color),
 }
@@ -22,6 +28,13 @@ impl RenderLayer {
             calls: Vec::new(),
         }
     }
+    /// A layer without a render target, which can be made without a window.
+    pub fn null() -> Self {
+        Self {
+            camera: Camera2D::default(),
+            calls: Vec::new(),
+        }
+    }
     pub fn draw(&mut self, assets: &Assets) {
         for call in self.calls.drain(..) {
             match call {
@@ -40,6 +53,15 @@ impl RenderLayer {
                 DrawCall::Rect(x, y, w, h, color) => {
                     draw_rectangle(x, y, w, h, color);
                 }
+                DrawCall::RectLines(x, y, w, h, color) => {
+                    draw_rectangle_lines(x, y, w, h, 1.0, color);
+                }
+                DrawCall::Line(x1, y1, x2, y2, color) => {
+                    draw_line(x1, y1, x2, y2, 1.0, color);
+                }
+                DrawCall::Text(text, x, y, color) => {
+                    draw_text(&text, x, y, 12.0, color);
+                }
                 DrawCall::Tileset(x, y, sx, sy) => {
                     assets.tileset.draw_sprite(sx, sy, x, y);
                 }
@@ -55,7 +77,8 @@ pub struct RenderLayers {
     pub world_foreground: RenderLayer,
     /// Render layer entities are drawn onto.
     pub entities: RenderLayer,
-    /// Unused as of yet. Should be unaffected to manipulations to the rendering.
+    /// Drawn over everything else, and not moved by the camera. The health bar, debug
+    /// overlay and menus go here.
     pub ui: RenderLayer,
 }
 
@@ -68,6 +91,25 @@ impl RenderLayers {
             ui: RenderLayer::new(),
         }
     }
+    /// Layers that are never drawn, for running screens without a window.
+    pub fn null() -> Self {
+        Self {
+            world: RenderLayer::null(),
+            entities: RenderLayer::null(),
+            world_foreground: RenderLayer::null(),
+            ui: RenderLayer::null(),
+        }
+    }
+    /// Points every layer but the ui at the given area of the world.
+    pub fn set_view(&mut self, view: Rect) {
+        for layer in [
+            &mut self.world,
+            &mut self.world_foreground,
+            &mut self.entities,
+        ] {
+            layer.camera.target = view.center();
+        }
+    }
     pub fn get_redrawn(&mut self) -> [&mut RenderLayer; 2] {
         [&mut self.entities, &mut self.ui]
     }
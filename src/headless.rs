@@ -0,0 +1,115 @@
+//! Runs the game's simulation without a window, for checking levels can be completed.
+//!
+//! Nothing here touches the graphics context, so it works anywhere, including on
+//! machines without a display.
+
+use crate::{
+    config::MovementConfig,
+    graphics::RenderLayers,
+    input::Input,
+    player::Player,
+    screens::{ScreenLink, ScreenRegistry, ScreenUpdateContext, ScreenUpdateResult},
+};
+
+/// How a headless run ended.
+pub struct Outcome {
+    /// How many ticks were simulated
+    pub ticks: usize,
+    /// The screen the run ended on
+    pub screen: String,
+    /// Every exit taken, as the tick it was taken on and where it led
+    pub exits: Vec<(usize, ScreenLink)>,
+    pub player: Player,
+}
+
+/// Plays `inputs` from `start`, one per tick, until they run out.
+///
+/// Screens are stepped exactly as the game steps them, so a replay ends up the same
+/// here as it does in a window. `start` must be a valid link into `screens`.
+pub fn run(
+    mut screens: ScreenRegistry,
+    start: ScreenLink,
+    config: &MovementConfig,
+    inputs: impl IntoIterator<Item = Input>,
+) -> Outcome {
+    let mut render_layers = RenderLayers::null();
+    let mut player = Player::new();
+    let mut current_screen = start.screen;
+    let mut spawn = Some(start.spawn);
+    let mut exits = Vec::new();
+    let mut ticks = 0;
+
+    macro_rules! ctx {
+        ($input:expr) => {
+            ScreenUpdateContext {
+                player: &mut player,
+                render_layers: &mut render_layers,
+                config,
+                debug_overlay: false,
+                input: $input,
+            }
+        };
+    }
+
+    for input in inputs {
+        let screen = screens
+            .get_mut(&current_screen)
+            .unwrap_or_else(|| panic!("Attempt to load unknown screen \"{current_screen}\""));
+        if let Some(name) = spawn.take() {
+            screen.on_load(ctx!(Input::default()), &name);
+        }
+        let result = screen.update(ctx!(input));
+        ticks += 1;
+        if let ScreenUpdateResult::ChangeScreen(link) = result {
+            current_screen = link.screen.clone();
+            spawn = Some(link.spawn.clone());
+            exits.push((ticks, link));
+        }
+        // nothing is ever drawn, so anything queued is thrown away
+        for layer in render_layers.get_all() {
+            layer.calls.clear();
+        }
+    }
+
+    Outcome {
+        ticks,
+        screen: current_screen,
+        exits,
+        player,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        input::{Action, ActionSet},
+        screens::{create_screen_registry, start_screen},
+    };
+
+    #[test]
+    fn running_and_jumping_right_takes_the_first_exit() {
+        let screens = create_screen_registry().unwrap();
+        let config = MovementConfig::built_in();
+        // held jumps repeat on landing, which gets over the block in the way
+        let held: ActionSet = [Action::MoveRight, Action::Jump].into_iter().collect();
+        let inputs = std::iter::repeat_n(Input::from_held(held, held), 300);
+        let outcome = run(screens, start_screen(), &config, inputs);
+        assert_eq!(outcome.ticks, 300);
+        let (tick, link) = &outcome.exits[0];
+        assert!(*tick < 300);
+        assert_eq!(*link, ScreenLink::new("2", "entrance"));
+        assert_eq!(outcome.screen, "2");
+    }
+
+    #[test]
+    fn standing_still_goes_nowhere() {
+        let screens = create_screen_registry().unwrap();
+        let config = MovementConfig::built_in();
+        let inputs = std::iter::repeat_n(Input::default(), 300);
+        let outcome = run(screens, start_screen(), &config, inputs);
+        assert!(outcome.exits.is_empty());
+        assert_eq!(outcome.screen, "1");
+        assert!(outcome.player.on_ground);
+    }
+}
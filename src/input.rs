@@ -0,0 +1,295 @@
+//! Player controls as actions instead of keys, so they can be rebound.
+
+use std::{collections::BTreeMap, fs, path::PathBuf};
+
+use enum_iterator::Sequence;
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Sequence, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    Jump,
+    Slide,
+    /// Drops through one-way platforms
+    Down,
+}
+
+/// A set of actions, one bit each.
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub struct ActionSet(pub u8);
+impl ActionSet {
+    pub fn contains(self, action: Action) -> bool {
+        self.0 & 1 << action as u8 != 0
+    }
+    pub fn insert(&mut self, action: Action) {
+        self.0 |= 1 << action as u8;
+    }
+}
+impl FromIterator<Action> for ActionSet {
+    fn from_iter<T: IntoIterator<Item = Action>>(actions: T) -> Self {
+        let mut set = Self::default();
+        for action in actions {
+            set.insert(action);
+        }
+        set
+    }
+}
+
+/// What the controls did during one tick.
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub struct Input {
+    /// Actions held down this tick
+    pub held: ActionSet,
+    /// Actions held this tick that weren't the tick before
+    pub pressed: ActionSet,
+    /// Actions held the tick before that aren't any more
+    pub released: ActionSet,
+}
+impl Input {
+    /// Works out a tick's input from what's held in it and the tick before.
+    pub fn from_held(held: ActionSet, last: ActionSet) -> Self {
+        Self {
+            held,
+            pressed: ActionSet(held.0 & !last.0),
+            released: ActionSet(last.0 & !held.0),
+        }
+    }
+    pub fn held(&self, action: Action) -> bool {
+        self.held.contains(action)
+    }
+    pub fn pressed(&self, action: Action) -> bool {
+        self.pressed.contains(action)
+    }
+    pub fn released(&self, action: Action) -> bool {
+        self.released.contains(action)
+    }
+}
+
+/// Turns the keyboard into one [Input] per tick.
+///
+/// Ticks don't line up with frames, so keys pressed during any frame are remembered
+/// until the next tick, and even a tap between two ticks counts as held for one.
+pub struct InputSampler {
+    /// What was held at the last tick
+    last: ActionSet,
+    /// Actions pressed since the last tick
+    tapped: ActionSet,
+}
+impl InputSampler {
+    pub fn new() -> Self {
+        Self {
+            last: ActionSet::default(),
+            tapped: ActionSet::default(),
+        }
+    }
+    /// Notes keys pressed this frame. Call once every frame.
+    pub fn poll(&mut self, bindings: &Bindings) {
+        self.tapped.0 |= bindings.actions(is_key_pressed).0;
+    }
+    /// The input for the next tick.
+    pub fn sample(&mut self, bindings: &Bindings) -> Input {
+        let held = ActionSet(bindings.actions(is_key_down).0 | self.tapped.0);
+        self.tapped = ActionSet::default();
+        self.advance(held)
+    }
+    /// The input for the next tick, given what's held in it.
+    pub(crate) fn advance(&mut self, held: ActionSet) -> Input {
+        let input = Input::from_held(held, self.last);
+        self.last = held;
+        input
+    }
+}
+
+/// Keys that can be bound, by the names they're saved under.
+const KEYS: [(&str, KeyCode); 72] = [
+    ("A", KeyCode::A),
+    ("B", KeyCode::B),
+    ("C", KeyCode::C),
+    ("D", KeyCode::D),
+    ("E", KeyCode::E),
+    ("F", KeyCode::F),
+    ("G", KeyCode::G),
+    ("H", KeyCode::H),
+    ("I", KeyCode::I),
+    ("J", KeyCode::J),
+    ("K", KeyCode::K),
+    ("L", KeyCode::L),
+    ("M", KeyCode::M),
+    ("N", KeyCode::N),
+    ("O", KeyCode::O),
+    ("P", KeyCode::P),
+    ("Q", KeyCode::Q),
+    ("R", KeyCode::R),
+    ("S", KeyCode::S),
+    ("T", KeyCode::T),
+    ("U", KeyCode::U),
+    ("V", KeyCode::V),
+    ("W", KeyCode::W),
+    ("X", KeyCode::X),
+    ("Y", KeyCode::Y),
+    ("Z", KeyCode::Z),
+    ("Key0", KeyCode::Key0),
+    ("Key1", KeyCode::Key1),
+    ("Key2", KeyCode::Key2),
+    ("Key3", KeyCode::Key3),
+    ("Key4", KeyCode::Key4),
+    ("Key5", KeyCode::Key5),
+    ("Key6", KeyCode::Key6),
+    ("Key7", KeyCode::Key7),
+    ("Key8", KeyCode::Key8),
+    ("Key9", KeyCode::Key9),
+    ("Space", KeyCode::Space),
+    ("Enter", KeyCode::Enter),
+    ("Tab", KeyCode::Tab),
+    ("Backspace", KeyCode::Backspace),
+    ("Up", KeyCode::Up),
+    ("Down", KeyCode::Down),
+    ("Left", KeyCode::Left),
+    ("Right", KeyCode::Right),
+    ("LeftShift", KeyCode::LeftShift),
+    ("RightShift", KeyCode::RightShift),
+    ("LeftControl", KeyCode::LeftControl),
+    ("RightControl", KeyCode::RightControl),
+    ("LeftAlt", KeyCode::LeftAlt),
+    ("RightAlt", KeyCode::RightAlt),
+    ("Apostrophe", KeyCode::Apostrophe),
+    ("Comma", KeyCode::Comma),
+    ("Minus", KeyCode::Minus),
+    ("Period", KeyCode::Period),
+    ("Slash", KeyCode::Slash),
+    ("Semicolon", KeyCode::Semicolon),
+    ("Equal", KeyCode::Equal),
+    ("LeftBracket", KeyCode::LeftBracket),
+    ("RightBracket", KeyCode::RightBracket),
+    ("Backslash", KeyCode::Backslash),
+    ("GraveAccent", KeyCode::GraveAccent),
+    ("Kp0", KeyCode::Kp0),
+    ("Kp1", KeyCode::Kp1),
+    ("Kp2", KeyCode::Kp2),
+    ("Kp3", KeyCode::Kp3),
+    ("Kp4", KeyCode::Kp4),
+    ("Kp5", KeyCode::Kp5),
+    ("Kp6", KeyCode::Kp6),
+    ("Kp7", KeyCode::Kp7),
+    ("Kp8", KeyCode::Kp8),
+    ("Kp9", KeyCode::Kp9),
+    ("KpEnter", KeyCode::KpEnter),
+];
+pub fn key_name(key: KeyCode) -> Option<&'static str> {
+    KEYS.iter().find(|(_, k)| *k == key).map(|(name, _)| *name)
+}
+fn key_from_name(name: &str) -> Option<KeyCode> {
+    KEYS.iter().find(|(n, _)| *n == name).map(|(_, key)| *key)
+}
+
+/// Which keys trigger each action.
+#[derive(Clone)]
+pub struct Bindings(BTreeMap<Action, Vec<KeyCode>>);
+impl Bindings {
+    pub fn defaults() -> Self {
+        Self(BTreeMap::from([
+            (Action::MoveLeft, vec![KeyCode::A]),
+            (Action::MoveRight, vec![KeyCode::D]),
+            (Action::Jump, vec![KeyCode::Space]),
+            (Action::Slide, vec![KeyCode::LeftShift]),
+            (Action::Down, vec![KeyCode::S]),
+        ]))
+    }
+    pub fn keys(&self, action: Action) -> &[KeyCode] {
+        self.0.get(&action).map(Vec::as_slice).unwrap_or_default()
+    }
+    pub fn set(&mut self, action: Action, keys: Vec<KeyCode>) {
+        self.0.insert(action, keys);
+    }
+    /// The actions with any key bound to them passing `check`, like [is_key_down].
+    fn actions(&self, check: impl Fn(KeyCode) -> bool) -> ActionSet {
+        self.0
+            .iter()
+            .filter(|(_, keys)| keys.iter().any(|key| check(*key)))
+            .map(|(action, _)| *action)
+            .collect()
+    }
+
+    /// Reads bindings saved as action names mapped to lists of key names.
+    ///
+    /// Actions that aren't listed keep their default keys.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let file: BTreeMap<Action, Vec<String>> =
+            toml::from_str(text).map_err(|e| e.to_string())?;
+        let mut bindings = Self::defaults();
+        for (action, names) in file {
+            let keys = names
+                .iter()
+                .map(|name| key_from_name(name).ok_or_else(|| format!("unknown key \"{name}\"")))
+                .collect::<Result<_, _>>()?;
+            bindings.set(action, keys);
+        }
+        Ok(bindings)
+    }
+    pub fn to_toml(&self) -> String {
+        let file: BTreeMap<Action, Vec<&str>> = self
+            .0
+            .iter()
+            .map(|(action, keys)| (*action, keys.iter().filter_map(|k| key_name(*k)).collect()))
+            .collect();
+        toml::to_string(&file).expect("bindings should always serialize")
+    }
+
+    /// Where the player's bindings are saved, if there's anywhere to save them.
+    fn file() -> Option<PathBuf> {
+        Some(dirs::config_dir()?.join("nora").join("controls.toml"))
+    }
+    /// Loads the player's saved bindings, or the defaults if there aren't any.
+    pub fn load() -> Self {
+        let Some(path) = Self::file() else {
+            return Self::defaults();
+        };
+        let Ok(text) = fs::read_to_string(&path) else {
+            return Self::defaults();
+        };
+        Self::parse(&text).unwrap_or_else(|e| {
+            eprintln!("failed to load controls from {}: {e}", path.display());
+            Self::defaults()
+        })
+    }
+    pub fn save(&self) {
+        let Some(path) = Self::file() else {
+            return;
+        };
+        let result = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&path, self.to_toml()));
+        if let Err(e) = result {
+            eprintln!("failed to save controls to {}: {e}", path.display());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_bindings_over_the_defaults() {
+        let bindings = Bindings::parse("Jump = [\"W\", \"Up\"]\nSlide = []").unwrap();
+        assert_eq!(bindings.keys(Action::Jump), [KeyCode::W, KeyCode::Up]);
+        assert_eq!(bindings.keys(Action::Slide), []);
+        assert_eq!(bindings.keys(Action::MoveLeft), [KeyCode::A]);
+
+        let saved = Bindings::parse(&bindings.to_toml()).unwrap();
+        for action in enum_iterator::all::<Action>() {
+            assert_eq!(saved.keys(action), bindings.keys(action));
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_keys_and_actions() {
+        let error = Bindings::parse("Jump = [\"W\", \"Nope\"]").err().unwrap();
+        assert_eq!(error, "unknown key \"Nope\"");
+        assert!(Bindings::parse("Fly = [\"W\"]").is_err());
+        assert!(Bindings::parse("Jump = \"W\"").is_err());
+    }
+}
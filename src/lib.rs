@@ -0,0 +1,18 @@
+// game state types are created explicitly, never defaulted
+#![allow(clippy::new_without_default)]
+
+pub mod assets;
+pub mod camera;
+pub mod config;
+pub mod debug;
+pub mod entity;
+pub mod graphics;
+pub mod headless;
+pub mod input;
+pub mod menu;
+pub mod nav;
+pub mod player;
+pub mod replay;
+pub mod screens;
+pub mod tmx;
+pub mod utils;
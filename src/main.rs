@@ -1,13 +1,19 @@
-use macroquad::{miniquad::window::screen_size, prelude::*, time};
+use std::{path::PathBuf, process::ExitCode};
 
-use crate::{assets::Assets, graphics::RenderLayers, player::Player, screens::*, utils::*};
+use macroquad::{miniquad::window::screen_size, prelude::*, time};
 
-mod assets;
-mod entity;
-mod graphics;
-mod player;
-mod screens;
-mod utils;
+use nora::{
+    assets::Assets,
+    config::{ConfigWatcher, MovementConfig},
+    graphics::RenderLayers,
+    headless,
+    input::{Bindings, Input, InputSampler},
+    menu::{ControlsMenu, PauseChoice, PauseMenu},
+    player::Player,
+    replay::{Replay, ReplayPlayer},
+    screens::*,
+    utils::*,
+};
 
 fn window_conf() -> Conf {
     Conf {
@@ -17,24 +23,325 @@ fn window_conf() -> Conf {
         ..Default::default()
     }
 }
-#[macroquad::main(window_conf)]
-async fn main() {
+/// Where each tick's input comes from, and whether it's being recorded.
+enum Session {
+    Live,
+    /// Recording into a replay, saved to the path when the game is closed
+    Recording(PathBuf, Replay),
+    Replaying(ReplayPlayer),
+}
+
+/// Options given on the command line.
+struct Args {
+    /// Where to save a recording of the run
+    record: Option<PathBuf>,
+    /// A recording to play back
+    replay: Option<PathBuf>,
+    /// Runs the simulation without a window, printing how it ended
+    headless: bool,
+    /// Where a headless run starts, instead of the replay's start
+    screen: Option<ScreenLink>,
+    /// Most ticks a headless run lasts
+    ticks: Option<usize>,
+}
+impl Args {
+    fn parse() -> Result<Self, String> {
+        let mut parsed = Self {
+            record: None,
+            replay: None,
+            headless: false,
+            screen: None,
+            ticks: None,
+        };
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or_else(|| format!("{arg} needs a value"));
+            match arg.as_str() {
+                "--record" => parsed.record = Some(PathBuf::from(value()?)),
+                "--replay" => parsed.replay = Some(PathBuf::from(value()?)),
+                "--headless" => parsed.headless = true,
+                // a screen on its own starts from the same spawn the game does
+                "--screen" => {
+                    let value = value()?;
+                    parsed.screen = Some(
+                        value
+                            .parse()
+                            .unwrap_or_else(|_| ScreenLink::new(&value, &start_screen().spawn)),
+                    );
+                }
+                "--ticks" => {
+                    let value = value()?;
+                    parsed.ticks = Some(
+                        value
+                            .parse()
+                            .map_err(|_| format!("invalid tick count \"{value}\""))?,
+                    );
+                }
+                _ => return Err(format!("unknown argument \"{arg}\"")),
+            }
+        }
+        if !parsed.headless && (parsed.screen.is_some() || parsed.ticks.is_some()) {
+            return Err("--screen and --ticks only work with --headless".to_string());
+        }
+        Ok(parsed)
+    }
+}
+
+fn main() -> ExitCode {
     println!("nora v{}", env!("CARGO_PKG_VERSION"));
+    let args = match Args::parse() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    if args.headless {
+        return run_headless(args);
+    }
+    macroquad::Window::from_config(window_conf(), game(args));
+    ExitCode::SUCCESS
+}
+
+/// Ticks a headless run without a replay lasts, unless told otherwise
+const DEFAULT_HEADLESS_TICKS: usize = 600;
+
+/// Runs the game without a window, then prints where the player ended up.
+fn run_headless(args: Args) -> ExitCode {
+    let mut start = start_screen();
+    let mut config = MovementConfig::built_in();
+    let mut replay = None;
+    if let Some(path) = &args.replay {
+        match Replay::load(path) {
+            Ok(loaded) => {
+                start = loaded.start.clone();
+                config = loaded.config.clone();
+                replay = Some(ReplayPlayer::new(loaded));
+            }
+            Err(e) => {
+                eprintln!("failed to load replay {}: {e}", path.display());
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    if let Some(screen) = args.screen {
+        start = screen;
+    }
+    let screens = match create_screen_registry() {
+        Ok(screens) => screens,
+        Err(e) => {
+            eprintln!("failed to load screen {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let problems = validate_screens(&screens, &start);
+    if !problems.is_empty() {
+        for (screen, problem) in problems.iter() {
+            eprintln!("screen \"{screen}\": {problem}");
+        }
+        eprintln!("found {} problems with screen links", problems.len());
+        return ExitCode::FAILURE;
+    }
+
+    // without a replay, the player just stands there
+    let inputs: Box<dyn Iterator<Item = Input>> = match replay {
+        Some(replay) => Box::new(replay.take(args.ticks.unwrap_or(usize::MAX))),
+        None => Box::new(std::iter::repeat_n(
+            Input::default(),
+            args.ticks.unwrap_or(DEFAULT_HEADLESS_TICKS),
+        )),
+    };
+    let outcome = headless::run(screens, start, &config, inputs);
+
+    for (tick, link) in &outcome.exits {
+        println!("tick {tick}: took exit to {}:{}", link.screen, link.spawn);
+    }
+    if outcome.exits.is_empty() {
+        println!("no exit taken");
+    }
+    let player = &outcome.player;
+    println!(
+        "ended on screen \"{}\" after {} ticks",
+        outcome.screen, outcome.ticks
+    );
+    println!("pos: {:.2}, {:.2}", player.pos.x, player.pos.y);
+    println!(
+        "velocity: {:.2}, {:.2}",
+        player.velocity.x, player.velocity.y
+    );
+    println!("health: {}/{}", player.health, player.max_health);
+    println!("on_ground: {}", player.on_ground);
+    println!("dead: {}", player.death_ticks.is_some());
+    ExitCode::SUCCESS
+}
+
+async fn game(args: Args) {
+    let Args { record, replay, .. } = args;
     let assets = Assets::load();
 
     let mut render_layers = RenderLayers::new();
 
     let mut player = Player::new();
 
-    let mut screens = create_screen_registry();
+    let mut config = MovementConfig::built_in();
+    let mut config_watcher = ConfigWatcher::new();
+    config_watcher.reload(&mut config);
+    let mut debug_overlay = false;
+
+    let mut bindings = Bindings::load();
+    let mut input_sampler = InputSampler::new();
+    let mut input = Input::default();
+    let mut controls_menu: Option<ControlsMenu> = None;
+
+    let mut screens = match create_screen_registry() {
+        Ok(screens) => screens,
+        Err(e) => {
+            eprintln!("failed to load screen {e}");
+            return;
+        }
+    };
     let mut last = time::get_time();
+    let mut accumulator = 0.0;
 
     set_default_camera();
 
-    let mut current_screen = screens::ScreenID::Level1;
-    let mut spawn: Option<usize> = Some(0);
+    let mut start = start_screen();
+    let mut session = Session::Live;
+    if let Some(path) = replay {
+        match Replay::load(&path) {
+            Ok(replay) => {
+                start = replay.start.clone();
+                config = replay.config.clone();
+                session = Session::Replaying(ReplayPlayer::new(replay));
+            }
+            Err(e) => {
+                eprintln!("failed to load replay {}: {e}", path.display());
+                return;
+            }
+        }
+    } else if let Some(path) = record {
+        // recordings are saved on the way out
+        prevent_quit();
+        session = Session::Recording(path, Replay::new(start.clone(), &config));
+    }
+    let problems = validate_screens(&screens, &start);
+    if !problems.is_empty() {
+        for (screen, problem) in problems.iter() {
+            eprintln!("screen \"{screen}\": {problem}");
+        }
+        eprintln!("found {} problems with screen links", problems.len());
+        return;
+    }
+    let mut current_screen = start.screen.clone();
+    let mut spawn: Option<String> = Some(start.spawn.clone());
+    // where the current screen was entered, for restarting it
+    let mut entered_at = start.spawn.clone();
+    let mut pause_menu: Option<PauseMenu> = None;
+    let mut was_paused = false;
+
+    // every call into a screen gets the same context
+    macro_rules! ctx {
+        () => {
+            ScreenUpdateContext {
+                player: &mut player,
+                render_layers: &mut render_layers,
+                config: &config,
+                debug_overlay,
+                input,
+            }
+        };
+    }
+    // the current screen, loaded first if it's just been entered
+    macro_rules! load_screen {
+        () => {{
+            let screen = screens
+                .get_mut(&current_screen)
+                .unwrap_or_else(|| panic!("Attempt to load unknown screen \"{current_screen}\""));
+            if let Some(name) = spawn.take() {
+                screen.on_load(ctx!(), &name);
+            }
+            screen
+        }};
+    }
 
     loop {
+        if is_quit_requested() {
+            if let Session::Recording(path, replay) = &session {
+                match replay.save(path) {
+                    Ok(()) => println!("saved replay to {}", path.display()),
+                    Err(e) => eprintln!("failed to save replay {}: {e}", path.display()),
+                }
+            }
+            break;
+        }
+        // the config can't change under a recording or replay, or it won't play back the same
+        if let Session::Live = session {
+            config_watcher.reload(&mut config);
+        }
+        if is_key_pressed(KeyCode::F3) {
+            debug_overlay = !debug_overlay;
+        }
+        if let Some(menu) = &mut controls_menu {
+            if !menu.update(&mut bindings) {
+                bindings.save();
+                controls_menu = None;
+            }
+        } else if let Some(menu) = &mut pause_menu {
+            match menu.update() {
+                None => {}
+                Some(PauseChoice::Resume) => pause_menu = None,
+                Some(PauseChoice::Settings) => controls_menu = Some(ControlsMenu::new()),
+                Some(choice @ (PauseChoice::RestartLevel | PauseChoice::QuitToTitle)) => {
+                    pause_menu = None;
+                    if choice == PauseChoice::QuitToTitle {
+                        current_screen = start.screen.clone();
+                        entered_at = start.spawn.clone();
+                    }
+                    // a restarted recording plays back from freshly loaded screens, so
+                    // every screen is reset for it, not just this one
+                    let recording = matches!(session, Session::Recording(..));
+                    let reset = if recording || choice == PauseChoice::QuitToTitle {
+                        create_screen_registry().map(|fresh| screens = fresh)
+                    } else {
+                        reset_screen(&mut screens, &current_screen)
+                    };
+                    if let Err(e) = reset {
+                        eprintln!("failed to reload screens {e}");
+                    }
+                    player = Player::new();
+                    spawn = Some(entered_at.clone());
+                    // playback starts with nothing held, so keys held through the restart
+                    // count as pressed on its first tick, both live and played back
+                    input_sampler = InputSampler::new();
+                    let restart = ScreenLink::new(&current_screen, &entered_at);
+                    match &mut session {
+                        Session::Live => {}
+                        Session::Recording(_, replay) => *replay = Replay::new(restart, &config),
+                        Session::Replaying(_) => session = Session::Live,
+                    }
+                }
+            }
+        } else if is_key_pressed(KeyCode::Escape) {
+            pause_menu = Some(PauseMenu::new());
+        } else if is_key_pressed(KeyCode::F1) {
+            controls_menu = Some(ControlsMenu::new());
+        }
+        let paused = pause_menu.is_some() || controls_menu.is_some();
+        // however a menu opened or closed, the screen hears about it
+        if paused != was_paused
+            && let Some(screen) = screens.get_mut(&current_screen)
+        {
+            if paused {
+                screen.on_pause(ctx!());
+            } else {
+                screen.on_resume(ctx!());
+            }
+        }
+        was_paused = paused;
+        if !paused {
+            input_sampler.poll(&bindings);
+        }
+
         clear_background(BLACK);
         let (actual_screen_width, actual_screen_height) = screen_size();
         let scale_factor =
@@ -43,38 +350,52 @@ async fn main() {
         let _mouse_x = mouse_x / scale_factor;
         let _mouse_y = mouse_y / scale_factor;
 
-        let screen = screens.get_mut(current_screen);
-
-        if let Some(i) = spawn {
-            spawn = None;
-            screen.on_load(
-                ScreenUpdateContext {
-                    player: &mut player,
-                    render_layers: &mut render_layers,
-                },
-                i,
-            );
-        }
+        let screen = load_screen!();
 
+        // run as many ticks as are owed, but give up on catching up after long hitches.
+        // time stands still while paused, so the frozen frame doesn't move either
         let now = time::get_time();
-        if now - last >= 1.0 / 60.0 {
-            last = now;
-            match screen.update(ScreenUpdateContext {
-                player: &mut player,
-                render_layers: &mut render_layers,
-            }) {
+        if !paused {
+            accumulator =
+                (accumulator + now - last).min(TICK_DURATION * MAX_TICKS_PER_FRAME as f64);
+        }
+        last = now;
+        while accumulator >= TICK_DURATION {
+            accumulator -= TICK_DURATION;
+            input = match &mut session {
+                Session::Replaying(replay) => match replay.next() {
+                    Some(input) => input,
+                    None => {
+                        println!("replay finished");
+                        session = Session::Live;
+                        input_sampler.sample(&bindings)
+                    }
+                },
+                _ => input_sampler.sample(&bindings),
+            };
+            if let Session::Recording(_, replay) = &mut session {
+                replay.inputs.push(input.held);
+            }
+            match screen.update(ctx!()) {
                 ScreenUpdateResult::Pass => {}
-                ScreenUpdateResult::ChangeScreen(screen, i) => {
-                    current_screen = screen;
-                    spawn = Some(i);
+                ScreenUpdateResult::ChangeScreen(link) => {
+                    current_screen = link.screen;
+                    entered_at = link.spawn.clone();
+                    spawn = Some(link.spawn);
+                    break;
                 }
             }
         }
 
-        screen.draw(ScreenUpdateContext {
-            player: &mut player,
-            render_layers: &mut render_layers,
-        });
+        // a screen changed to this frame is drawn instead of the one that was left
+        load_screen!().draw(ctx!(), (accumulator / TICK_DURATION) as f32);
+
+        if let Some(menu) = &pause_menu {
+            menu.draw(&mut render_layers.ui);
+        }
+        if let Some(menu) = &controls_menu {
+            menu.draw(&mut render_layers.ui, &bindings);
+        }
 
         // draw cameras
         for layer in render_layers.get_all().into_iter() {
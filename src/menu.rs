@@ -0,0 +1,171 @@
+//! Menus drawn over the game while it's paused.
+
+use macroquad::prelude::*;
+
+use crate::{
+    graphics::{DrawCall, RenderLayer},
+    input::{Action, Bindings, key_name},
+    utils::*,
+};
+
+/// Lists every action's keys, and lets them be rebound.
+///
+/// Navigated with fixed keys, so bad bindings can always be fixed.
+pub struct ControlsMenu {
+    /// Index of the highlighted action
+    selected: usize,
+    /// Whether the next key pressed will be bound to the highlighted action
+    rebinding: bool,
+}
+impl ControlsMenu {
+    pub fn new() -> Self {
+        Self {
+            selected: 0,
+            rebinding: false,
+        }
+    }
+    fn actions() -> Vec<Action> {
+        enum_iterator::all::<Action>().collect()
+    }
+    /// Handles this frame's keys. Returns `false` once the menu is closed.
+    pub fn update(&mut self, bindings: &mut Bindings) -> bool {
+        let actions = Self::actions();
+        if self.rebinding {
+            if let Some(key) = get_last_key_pressed() {
+                self.rebinding = false;
+                // escape cancels, and keys that can't be saved are ignored
+                if key != KeyCode::Escape && key_name(key).is_some() {
+                    bindings.set(actions[self.selected], vec![key]);
+                }
+            }
+            return true;
+        }
+        if is_key_pressed(KeyCode::Up) {
+            self.selected = (self.selected + actions.len() - 1) % actions.len();
+        }
+        if is_key_pressed(KeyCode::Down) {
+            self.selected = (self.selected + 1) % actions.len();
+        }
+        if is_key_pressed(KeyCode::Enter) {
+            self.rebinding = true;
+        }
+        if is_key_pressed(KeyCode::R) {
+            *bindings = Bindings::defaults();
+        }
+        !is_key_pressed(KeyCode::Escape)
+    }
+    pub fn draw(&self, layer: &mut RenderLayer, bindings: &Bindings) {
+        layer.calls.push(DrawCall::Rect(
+            0.0,
+            0.0,
+            SCREEN_WIDTH,
+            SCREEN_HEIGHT,
+            BLACK.with_alpha(0.75),
+        ));
+        layer
+            .calls
+            .push(DrawCall::Text("controls".to_string(), 16.0, 24.0, WHITE));
+        for (i, action) in Self::actions().into_iter().enumerate() {
+            let keys = bindings
+                .keys(action)
+                .iter()
+                .filter_map(|key| key_name(*key))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let selected = i == self.selected;
+            let keys = if selected && self.rebinding {
+                "press a key...".to_string()
+            } else {
+                keys
+            };
+            let y = 48.0 + i as f32 * 14.0;
+            let color = if selected { YELLOW } else { WHITE };
+            let marker = if selected { ">" } else { " " };
+            layer.calls.push(DrawCall::Text(
+                format!("{marker} {action:?}"),
+                16.0,
+                y,
+                color,
+            ));
+            layer.calls.push(DrawCall::Text(keys, 140.0, y, color));
+        }
+        layer.calls.push(DrawCall::Text(
+            "up/down: select  enter: rebind  r: reset  esc: back".to_string(),
+            16.0,
+            SCREEN_HEIGHT - 16.0,
+            GRAY,
+        ));
+    }
+}
+
+/// What was picked from the pause menu.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PauseChoice {
+    Resume,
+    /// Starts the current screen over from where it was entered
+    RestartLevel,
+    Settings,
+    /// Goes back to the start of the game, as there's no title screen yet
+    QuitToTitle,
+}
+const PAUSE_CHOICES: [(&str, PauseChoice); 4] = [
+    ("resume", PauseChoice::Resume),
+    ("restart level", PauseChoice::RestartLevel),
+    ("settings", PauseChoice::Settings),
+    ("quit to title", PauseChoice::QuitToTitle),
+];
+
+/// Shown over the frozen game while it's paused.
+pub struct PauseMenu {
+    /// Index of the highlighted choice
+    selected: usize,
+}
+impl PauseMenu {
+    pub fn new() -> Self {
+        Self { selected: 0 }
+    }
+    /// Handles this frame's keys, returning what was picked, if anything.
+    ///
+    /// Escape picks [PauseChoice::Resume].
+    pub fn update(&mut self) -> Option<PauseChoice> {
+        if is_key_pressed(KeyCode::Up) {
+            self.selected = (self.selected + PAUSE_CHOICES.len() - 1) % PAUSE_CHOICES.len();
+        }
+        if is_key_pressed(KeyCode::Down) {
+            self.selected = (self.selected + 1) % PAUSE_CHOICES.len();
+        }
+        if is_key_pressed(KeyCode::Escape) {
+            Some(PauseChoice::Resume)
+        } else if is_key_pressed(KeyCode::Enter) {
+            Some(PAUSE_CHOICES[self.selected].1)
+        } else {
+            None
+        }
+    }
+    pub fn draw(&self, layer: &mut RenderLayer) {
+        layer.calls.push(DrawCall::Rect(
+            0.0,
+            0.0,
+            SCREEN_WIDTH,
+            SCREEN_HEIGHT,
+            BLACK.with_alpha(0.6),
+        ));
+        layer
+            .calls
+            .push(DrawCall::Text("paused".to_string(), 16.0, 24.0, WHITE));
+        for (i, (label, _)) in PAUSE_CHOICES.iter().enumerate() {
+            let selected = i == self.selected;
+            let (marker, color) = if selected {
+                (">", YELLOW)
+            } else {
+                (" ", WHITE)
+            };
+            layer.calls.push(DrawCall::Text(
+                format!("{marker} {label}"),
+                16.0,
+                48.0 + i as f32 * 14.0,
+                color,
+            ));
+        }
+    }
+}
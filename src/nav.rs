@@ -0,0 +1,451 @@
+//! Navigation for entities that walk and jump, so they can find their way around a map
+//! without hints painted into it.
+//!
+//! The graph's nodes are the tiles a humanoid can stand in, by the tile its feet are in,
+//! which is the tile [TALL_HITBOX](crate::entity::TALL_HITBOX)'s position rounds to.
+
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap},
+};
+
+use macroquad::prelude::*;
+
+use crate::screens::{CollisionTile, Map};
+
+/// A tile, as its column and row.
+pub type Tile = (i32, i32);
+
+/// How an entity moves and jumps, for working out where its jumps can reach.
+pub struct JumpArc {
+    /// Upwards force of the jump, applied on the tick it starts
+    pub impulse: f32,
+    pub gravity: f32,
+    /// Strongest sideways force the entity steers with, as passed to [steer]
+    pub force: f32,
+    /// Fraction of sideways velocity lost each tick in the air
+    pub air_drag: f32,
+    /// Top speed on the ground, in pixels per tick
+    pub run_speed: f32,
+}
+
+/// Fraction of the distance left to a target an entity steering at it tries to cover
+/// each tick
+const STEER_GAIN: f32 = 0.25;
+
+/// The sideways force that brings an entity to a stop at a point `distance` away,
+/// without overshooting it, given its `velocity` and the `drag` it's under.
+///
+/// Entities steering with this on the ground and in the air move exactly as their
+/// graph's jumps expect.
+pub fn steer(distance: f32, velocity: f32, force: f32, drag: f32) -> f32 {
+    let wanted = distance * STEER_GAIN;
+    (wanted - velocity * (1.0 - drag)).clamp(-force, force)
+}
+
+/// How to get from one node to the next.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Move {
+    /// Walk across, or up or down a ramp
+    Walk,
+    /// Jump from the previous node
+    Jump,
+    /// Walk off a ledge and fall
+    Drop,
+    /// Fall down through the one-way platform underfoot
+    DropThrough,
+}
+
+/// One step of a path: the node to get to, and how.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Step {
+    pub tile: Tile,
+    pub movement: Move,
+}
+
+#[derive(Clone, Copy)]
+struct Edge {
+    to: Tile,
+    movement: Move,
+    /// Roughly how many ticks the move takes
+    cost: f32,
+}
+
+/// Extra cost of every jump, in ticks, so entities walk where they can instead of
+/// hopping everywhere
+const JUMP_COST: f32 = 30.0;
+/// Furthest a jump is aimed to either side, in tiles
+const JUMP_RANGE: i32 = 10;
+/// Longest a jump is followed for before giving up on it landing anywhere
+const MAX_JUMP_TICKS: usize = 120;
+
+/// Everywhere an entity can stand in a map, and how it can move between them.
+pub struct NavGraph {
+    width: i32,
+    height: i32,
+    /// Moves out of each tile, by tile index. Tiles that can't be stood in have none.
+    edges: Vec<Vec<Edge>>,
+    /// Whether each tile can be stood in, by tile index
+    nodes: Vec<bool>,
+    /// Ticks it takes to walk one tile
+    walk_cost: f32,
+    /// Fewest ticks it could take to cross one tile, walking or jumping, used to
+    /// estimate the rest of a path
+    tile_cost: f32,
+}
+
+/// Whether a tile can be passed through, or stood in.
+fn passable(map: &Map, (x, y): Tile) -> bool {
+    map.collision_tile(x, y) != CollisionTile::Solid
+}
+/// Whether a humanoid could stand with its feet in `tile`.
+fn standable(map: &Map, (x, y): Tile) -> bool {
+    if !passable(map, (x, y)) || !passable(map, (x, y - 1)) {
+        return false;
+    }
+    matches!(map.collision_tile(x, y), CollisionTile::Slope(..))
+        || matches!(
+            map.collision_tile(x, y + 1),
+            CollisionTile::Solid | CollisionTile::Platform | CollisionTile::Slope(..)
+        )
+}
+/// Whether a standing humanoid with its feet at `pos` overlaps any solid tiles.
+fn body_blocked(map: &Map, pos: Vec2) -> bool {
+    let (left, right) = (
+        (pos.x / 8.0).floor() as i32,
+        ((pos.x + 8.0) / 8.0).ceil() as i32,
+    );
+    let (top, bottom) = (
+        ((pos.y - 8.0) / 8.0).floor() as i32,
+        ((pos.y + 8.0) / 8.0).ceil() as i32,
+    );
+    (left..right).any(|x| (top..bottom).any(|y| !passable(map, (x, y))))
+}
+
+impl NavGraph {
+    pub fn build(map: &Map, arc: &JumpArc) -> Self {
+        let (width, height) = (map.width as i32, map.height as i32);
+        let tiles = || (0..height).flat_map(move |y| (0..width).map(move |x| (x, y)));
+        let nodes: Vec<bool> = tiles().map(|tile| standable(map, tile)).collect();
+        let mut graph = Self {
+            width,
+            height,
+            edges: vec![Vec::new(); nodes.len()],
+            nodes,
+            walk_cost: 8.0 / arc.run_speed,
+            tile_cost: 8.0 / arc.run_speed.max(arc.force / arc.air_drag),
+        };
+        let nodes: Vec<Tile> = tiles().filter(|&tile| graph.is_node(tile)).collect();
+        for tile in nodes {
+            let mut edges = graph.walk_edges(map, tile);
+            edges.extend(graph.jump_edges(map, arc, tile));
+            // only the quickest way to each node is worth keeping
+            let mut quickest: HashMap<Tile, Edge> = HashMap::new();
+            for edge in edges {
+                match quickest.get(&edge.to) {
+                    Some(existing) if existing.cost <= edge.cost => {}
+                    _ => {
+                        quickest.insert(edge.to, edge);
+                    }
+                }
+            }
+            let index = graph.index(tile).unwrap();
+            graph.edges[index] = quickest.into_values().collect();
+            graph.edges[index].sort_by_key(|edge| edge.to);
+        }
+        graph
+    }
+
+    fn index(&self, (x, y): Tile) -> Option<usize> {
+        ((0..self.width).contains(&x) && (0..self.height).contains(&y))
+            .then(|| (x + y * self.width) as usize)
+    }
+    /// Whether the tile can be stood in.
+    pub fn is_node(&self, tile: Tile) -> bool {
+        self.index(tile).is_some_and(|index| self.nodes[index])
+    }
+    /// The node the first solid ground at or below `tile` can be stood on from, if any.
+    fn land(&self, map: &Map, (x, y): Tile) -> Option<Tile> {
+        (y..self.height)
+            .take_while(|&y| passable(map, (x, y)))
+            .find(|&y| self.is_node((x, y)))
+            .map(|y| (x, y))
+    }
+    /// The node an entity at `pos` is standing in, or would land in if it fell.
+    pub fn node_below(&self, map: &Map, pos: Vec2) -> Option<Tile> {
+        let x = (pos.x / 8.0).round() as i32;
+        let y = ((pos.y + 8.0 - 0.01) / 8.0).floor() as i32;
+        self.land(map, (x, y.max(0)))
+    }
+
+    /// Walking to either side, up and down ramps, and off ledges.
+    fn walk_edges(&self, map: &Map, (x, y): Tile) -> Vec<Edge> {
+        let mut edges = Vec::new();
+        let slope = |(x, y): Tile| matches!(map.collision_tile(x, y), CollisionTile::Slope(..));
+        for side in [-1, 1] {
+            let beside = (x + side, y);
+            if self.is_node(beside) {
+                edges.push(Edge {
+                    to: beside,
+                    movement: Move::Walk,
+                    cost: self.walk_cost,
+                });
+                continue;
+            }
+            // ramps lead up or down a row
+            let ramp = [(x + side, y - 1), (x + side, y + 1)]
+                .into_iter()
+                .find(|&to| self.is_node(to) && (slope((x, y)) || slope(to)));
+            if let Some(to) = ramp {
+                edges.push(Edge {
+                    to,
+                    movement: Move::Walk,
+                    cost: self.walk_cost,
+                });
+                continue;
+            }
+            if passable(map, beside)
+                && passable(map, (x + side, y - 1))
+                && let Some(to) = self.land(map, beside)
+            {
+                edges.push(Edge {
+                    to,
+                    movement: Move::Drop,
+                    cost: self.walk_cost + fall_ticks(to.1 - y),
+                });
+            }
+        }
+        if map.collision_tile(x, y + 1) == CollisionTile::Platform
+            && let Some(to) = self.land(map, (x, y + 1))
+        {
+            edges.push(Edge {
+                to,
+                movement: Move::DropThrough,
+                cost: fall_ticks(to.1 - y),
+            });
+        }
+        edges
+    }
+
+    /// Everywhere a jump from `from` lands, aiming at each column in reach.
+    ///
+    /// Each jump is followed tick by tick, starting from standing still and steering
+    /// with [steer] at the column, as the entity will when it takes the jump.
+    fn jump_edges(&self, map: &Map, arc: &JumpArc, from: Tile) -> Vec<Edge> {
+        let mut edges = Vec::new();
+        let start = Vec2::new(from.0 as f32 * 8.0, from.1 as f32 * 8.0);
+        for column in from.0 - JUMP_RANGE..=from.0 + JUMP_RANGE {
+            let aim = column as f32 * 8.0;
+            let mut pos = start;
+            let mut velocity = Vec2::new(0.0, -arc.impulse);
+            for tick in 1..=MAX_JUMP_TICKS {
+                velocity.x += steer(aim - pos.x, velocity.x, arc.force, arc.air_drag)
+                    - velocity.x * arc.air_drag;
+                velocity.y += arc.gravity;
+                let last_y = pos.y;
+                pos += velocity;
+                if velocity.y > 0.0 {
+                    // rows whose floor the feet passed on the way down
+                    let column = (pos.x / 8.0).round() as i32;
+                    let rows = (last_y / 8.0).floor() as i32 + 1..=(pos.y / 8.0).floor() as i32;
+                    let landing = rows
+                        .map(|row| (column, row))
+                        .find(|&tile| self.is_node(tile));
+                    if let Some(to) = landing {
+                        let clear = !body_blocked(map, Vec2::new(pos.x, to.1 as f32 * 8.0));
+                        if clear && to != from {
+                            edges.push(Edge {
+                                to,
+                                movement: Move::Jump,
+                                cost: tick as f32 + JUMP_COST,
+                            });
+                        }
+                        break;
+                    }
+                }
+                if body_blocked(map, pos) {
+                    break;
+                }
+            }
+        }
+        edges
+    }
+
+    /// Finds the quickest way from one node to another, as the steps after `from`.
+    pub fn find_path(&self, from: Tile, to: Tile) -> Option<Vec<Step>> {
+        if !self.is_node(from) || !self.is_node(to) {
+            return None;
+        }
+        let estimate = |tile: Tile| (tile.0 - to.0).abs() as f32 * self.tile_cost;
+        let mut open = BinaryHeap::from([Open {
+            estimate: estimate(from),
+            tile: from,
+        }]);
+        let mut costs = HashMap::from([(from, 0.0)]);
+        let mut came_from: HashMap<Tile, (Tile, Move)> = HashMap::new();
+        while let Some(Open { tile, .. }) = open.pop() {
+            if tile == to {
+                let mut path = Vec::new();
+                let mut tile = to;
+                while let Some(&(previous, movement)) = came_from.get(&tile) {
+                    path.push(Step { tile, movement });
+                    tile = previous;
+                }
+                path.reverse();
+                return Some(path);
+            }
+            let cost = costs[&tile];
+            for edge in &self.edges[self.index(tile).unwrap()] {
+                let new_cost = cost + edge.cost;
+                if costs.get(&edge.to).is_none_or(|&old| new_cost < old) {
+                    costs.insert(edge.to, new_cost);
+                    came_from.insert(edge.to, (tile, edge.movement));
+                    open.push(Open {
+                        estimate: new_cost + estimate(edge.to),
+                        tile: edge.to,
+                    });
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Roughly how many ticks falling `rows` tiles takes.
+fn fall_ticks(rows: i32) -> f32 {
+    (rows.max(0) as f32 * 8.0).sqrt()
+}
+
+/// A node waiting to be searched by [NavGraph::find_path], most promising first.
+struct Open {
+    estimate: f32,
+    tile: Tile,
+}
+impl PartialEq for Open {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+impl Eq for Open {}
+impl PartialOrd for Open {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for Open {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // the heap pops the largest, so lower estimates are greater
+        other
+            .estimate
+            .total_cmp(&self.estimate)
+            .then_with(|| other.tile.cmp(&self.tile))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ARC: JumpArc = JumpArc {
+        impulse: 8.5,
+        gravity: 0.9,
+        force: 0.4,
+        air_drag: 0.07,
+        run_speed: 1.9,
+    };
+
+    fn movements(path: &[Step]) -> Vec<Move> {
+        path.iter().map(|step| step.movement).collect()
+    }
+
+    #[test]
+    fn walks_along_flat_ground() {
+        let map = Map::from_ascii(
+            "
+            ......
+            ......
+            ######
+            ",
+        );
+        let nav = NavGraph::build(&map, &ARC);
+        let path = nav.find_path((0, 1), (3, 1)).unwrap();
+        assert_eq!(movements(&path), [Move::Walk; 3]);
+        assert_eq!(path.last().unwrap().tile, (3, 1));
+    }
+
+    #[test]
+    fn jumps_up_onto_a_ledge() {
+        let map = Map::from_ascii(
+            "
+            ........
+            ........
+            ........
+            .....###
+            .....###
+            ########
+            ",
+        );
+        let nav = NavGraph::build(&map, &ARC);
+        let path = nav.find_path((1, 4), (6, 2)).unwrap();
+        assert!(movements(&path).contains(&Move::Jump));
+        assert_eq!(path.last().unwrap().tile, (6, 2));
+    }
+
+    #[test]
+    fn cant_jump_a_wall_too_high() {
+        let map = Map::from_ascii(
+            "
+            ........
+            .....###
+            .....###
+            .....###
+            .....###
+            .....###
+            .....###
+            .....###
+            ########
+            ",
+        );
+        let nav = NavGraph::build(&map, &ARC);
+        assert!(nav.find_path((1, 7), (6, 0)).is_none());
+    }
+
+    #[test]
+    fn drops_off_ledges_and_through_platforms() {
+        let map = Map::from_ascii(
+            "
+            ........
+            ###.....
+            ........
+            ...---..
+            ........
+            ........
+            ########
+            ",
+        );
+        let nav = NavGraph::build(&map, &ARC);
+        let path = nav.find_path((2, 0), (3, 2)).unwrap();
+        assert_eq!(movements(&path), [Move::Drop]);
+        let path = nav.find_path((4, 2), (4, 5)).unwrap();
+        assert_eq!(movements(&path), [Move::DropThrough]);
+    }
+
+    #[test]
+    fn walks_up_ramps() {
+        let map = Map::from_ascii(
+            "
+            ........
+            ........
+            ...../##
+            ########
+            ",
+        );
+        let nav = NavGraph::build(&map, &ARC);
+        let path = nav.find_path((4, 2), (6, 1)).unwrap();
+        assert!(
+            movements(&path)
+                .iter()
+                .all(|&movement| movement == Move::Walk)
+        );
+    }
+}
@@ -2,77 +2,185 @@ use macroquad::prelude::*;
 
 use crate::{
     assets::*,
-    entity::update_physics_entity,
+    entity::{SHORT_HITBOX, TALL_HITBOX, World, update_physics_entity},
     graphics::{DrawCall, RenderLayer},
-    screens::Map,
-    utils::*,
+    input::{Action, Input},
 };
 
+/// How long the player can't be hurt for after taking a hit
+const INVULNERABLE_TICKS: u32 = 60;
+/// How long the death animation plays before respawning
+const DEATH_TICKS: u32 = 90;
+/// Velocity the player is knocked back with when hit, away from the source
+const KNOCKBACK: Vec2 = Vec2::new(2.0, -3.0);
+
 pub struct Player {
     pub pos: Vec2,
-    pub camera_pos: Vec2,
+    pub last_pos: Vec2,
     pub velocity: Vec2,
     pub anim_frame: u32,
     pub facing_right: bool,
     pub on_ground: bool,
     pub head_covered: bool,
+    /// Whether a wall is right up against the player's left
+    pub wall_left: bool,
+    /// Whether a wall is right up against the player's right
+    pub wall_right: bool,
+    /// Whether the player is pressing into a wall while falling
+    pub wall_sliding: bool,
+    /// Ticks left until left and right work again after a wall jump
+    pub wall_jump_lockout: u32,
     pub jump_frames: u8,
+    /// Ticks left in which a jump is allowed without being on the ground
+    coyote_ticks: u32,
+    /// Ticks left until a buffered jump is forgotten
+    buffered_jump_ticks: u32,
     pub standing: bool,
     pub health: f32,
     pub max_health: f32,
+    /// Ticks left until the player can be hurt again
+    pub invulnerable_ticks: u32,
+    /// Ticks since the player died, if they're dead
+    pub death_ticks: Option<u32>,
 }
 impl Player {
     pub fn new() -> Self {
         Self {
             // public states
             pos: Vec2::ZERO,
-            camera_pos: Vec2::ZERO,
+            last_pos: Vec2::ZERO,
             velocity: Vec2::ZERO,
             max_health: 100.0,
             health: 100.0,
+            invulnerable_ticks: 0,
+            death_ticks: None,
 
             // internal states
             anim_frame: 0,
             jump_frames: 0,
+            coyote_ticks: 0,
+            buffered_jump_ticks: 0,
             facing_right: true,
             on_ground: false,
             head_covered: false,
+            wall_left: false,
+            wall_right: false,
+            wall_sliding: false,
+            wall_jump_lockout: 0,
             standing: true,
         }
     }
     fn can_slide(&self) -> bool {
         true
     }
-    pub fn update(&mut self, map: &Map) {
+    /// The player's hitbox in world pixels, which is shorter while sliding.
+    pub fn hitbox(&self) -> Rect {
+        if self.standing {
+            TALL_HITBOX.offset(self.pos)
+        } else {
+            SHORT_HITBOX.offset(self.pos)
+        }
+    }
+    pub fn is_dead(&self) -> bool {
+        self.death_ticks.is_some()
+    }
+    /// Whether the death animation is over, and the player should be respawned.
+    pub fn should_respawn(&self) -> bool {
+        self.death_ticks.is_some_and(|ticks| ticks >= DEATH_TICKS)
+    }
+    /// Hurts the player and knocks them away from `source`, unless they were hit recently.
+    pub fn damage(&mut self, amount: f32, source: Vec2) {
+        if self.is_dead() || self.invulnerable_ticks > 0 {
+            return;
+        }
+        self.health -= amount;
+        self.invulnerable_ticks = INVULNERABLE_TICKS;
+        let direction = if self.pos.x < source.x { -1.0 } else { 1.0 };
+        self.velocity = Vec2::new(KNOCKBACK.x * direction, KNOCKBACK.y);
+        if self.health <= 0.0 {
+            self.health = 0.0;
+            self.death_ticks = Some(0);
+        }
+    }
+    /// Kills the player outright, whatever their health.
+    pub fn kill(&mut self) {
+        if self.is_dead() {
+            return;
+        }
+        self.health = 0.0;
+        self.death_ticks = Some(0);
+    }
+    /// Brings the player back to life at `pos`.
+    pub fn respawn(&mut self, pos: Vec2) {
+        self.pos = pos;
+        self.last_pos = pos;
+        self.velocity = Vec2::ZERO;
+        self.health = self.max_health;
+        self.death_ticks = None;
+        self.invulnerable_ticks = INVULNERABLE_TICKS;
+        self.standing = true;
+    }
+    pub fn update(&mut self, world: &World, input: Input) {
+        let config = world.config;
         self.anim_frame += 1000 / 60;
+        self.last_pos = self.pos;
+        self.invulnerable_ticks = self.invulnerable_ticks.saturating_sub(1);
+        self.wall_jump_lockout = self.wall_jump_lockout.saturating_sub(1);
+        if let Some(ticks) = &mut self.death_ticks {
+            *ticks += 1;
+        }
+        // the dead can't move
+        let input = if self.is_dead() {
+            Input::default()
+        } else {
+            input
+        };
 
         let mut forces = Vec2::ZERO;
 
-        let mut speed = PLAYER_SPEED;
+        let mut speed = config.run_force;
         let can_slide = self.can_slide();
-        let shift_pressed = is_key_down(KeyCode::LeftShift);
+        let slide_held = input.held(Action::Slide);
 
         if self.standing {
-            if shift_pressed && can_slide {
+            if slide_held && can_slide {
                 self.standing = false;
-                speed *= 1.5;
+                speed *= config.slide_boost;
             }
         } else {
-            if !shift_pressed && !self.head_covered {
+            if !slide_held && !self.head_covered {
                 self.standing = true;
             }
             speed = 0.0;
-            if self.velocity.x.abs() < 0.5 && self.head_covered {
-                forces.x += 0.5 * if self.facing_right { 1.0 } else { -1.0 };
+            if self.velocity.x.abs() < config.stuck_slide_speed && self.head_covered {
+                forces.x += config.stuck_slide_force * if self.facing_right { 1.0 } else { -1.0 };
             }
         }
 
-        if speed > 0.0 {
-            if is_key_down(KeyCode::A) {
+        if input.pressed(Action::Jump) {
+            self.buffered_jump_ticks = config.jump_buffer.max(1);
+        }
+        let jump_wanted = self.buffered_jump_ticks > 0;
+        self.buffered_jump_ticks = self.buffered_jump_ticks.saturating_sub(1);
+        let can_jump = self.on_ground || self.coyote_ticks > 0;
+
+        // kick off walls before steering, so pressing into the wall doesn't cancel it
+        let wall_jump =
+            !can_jump && self.standing && (self.wall_left || self.wall_right) && jump_wanted;
+        if wall_jump {
+            let away = if self.wall_left { 1.0 } else { -1.0 };
+            self.velocity = Vec2::new(config.wall_jump_kick * away, 0.0);
+            self.facing_right = away > 0.0;
+            self.wall_jump_lockout = config.wall_jump_lockout;
+            self.jump_frames = 0;
+        }
+
+        if speed > 0.0 && self.wall_jump_lockout == 0 {
+            if input.held(Action::MoveLeft) {
                 forces.x -= speed;
                 self.facing_right = false;
             }
-            if is_key_down(KeyCode::D) {
+            if input.held(Action::MoveRight) {
                 forces.x += speed;
                 self.facing_right = true;
             }
@@ -81,15 +189,25 @@ impl Player {
         if self.on_ground {
             self.jump_frames = 0;
         }
-        if is_key_down(KeyCode::Space)
-            && (self.on_ground || (self.jump_frames > 0 && self.jump_frames < 5))
+        // holding jump on the ground jumps again on landing, as before buffering
+        let jump_start = wall_jump || (can_jump && (jump_wanted || input.held(Action::Jump)));
+        if jump_start {
+            self.jump_frames = 0;
+            self.coyote_ticks = 0;
+            self.buffered_jump_ticks = 0;
+        }
+        let hold_ticks = config.jump_hold_ticks;
+        if jump_start
+            || (input.held(Action::Jump) && self.jump_frames > 0 && self.jump_frames < hold_ticks)
         {
             forces.y -= if self.jump_frames == 0 {
-                3.5
+                config.jump_impulse
             } else {
-                1.5 * (5 - self.jump_frames) as f32 / 2.5
+                config.jump_hold_force * (hold_ticks - self.jump_frames) as f32
+                    / (hold_ticks as f32 / 2.0)
             } * if !self.standing {
-                self.velocity.x.abs().min(2.0) / 2.0
+                self.velocity.x.abs().min(config.slide_jump_full_speed)
+                    / config.slide_jump_full_speed
             } else {
                 1.0
             };
@@ -98,20 +216,29 @@ impl Player {
 
         forces.x -= self.velocity.x
             * if !self.standing {
-                0.02
+                config.slide_friction
             } else if self.on_ground {
-                GROUND_FRICTION
+                config.ground_friction
             } else {
-                AIR_DRAG
+                config.air_drag
             };
         let old_velocity = self.velocity;
-        let (on_ground, head_covered) = update_physics_entity(
+        let contacts = update_physics_entity(
             &mut self.pos,
             &mut forces,
             &mut self.velocity,
-            self.standing,
-            map,
+            if self.standing {
+                TALL_HITBOX
+            } else {
+                SHORT_HITBOX
+            },
+            input.held(Action::Down),
+            world,
         );
+        if contacts.crushed {
+            self.kill();
+        }
+        let on_ground = contacts.on_ground();
         if !self.on_ground
             && on_ground
             && !self.standing
@@ -119,41 +246,434 @@ impl Player {
             && old_velocity.y > 0.0
         {
             let m = if self.facing_right { 1.0 } else { -1.0 };
-
-            self.velocity.x += (1.0 / (old_velocity.y) + 2.0) * m;
+            let boost =
+                config.slide_landing_soft_boost / old_velocity.y + config.slide_landing_boost;
+            self.velocity.x += boost.min(config.max_slide_landing_boost) * m;
+        }
+        // slides pick up speed going downhill
+        if on_ground && !self.standing {
+            self.velocity.x += contacts.ground_normal.x * config.slope_slide_acceleration;
         }
         if self.standing {
-            self.velocity.x = self.velocity.x.clamp(-MAX_RUN_VELOCITY, MAX_RUN_VELOCITY);
+            self.velocity.x = self
+                .velocity
+                .x
+                .clamp(-config.max_run_velocity, config.max_run_velocity);
         }
-        self.on_ground = on_ground;
-        self.head_covered = head_covered;
-
-        self.camera_pos.x = self.pos.x.floor();
-        let delta = self.camera_pos.y - self.pos.y.floor();
-        let max_delta = 3.0 * 8.0;
-        if delta.abs() >= max_delta {
-            self.camera_pos.y =
-                max_delta * if delta < 0.0 { -1.0 } else { 1.0 } + self.pos.y.floor();
+        self.wall_sliding = !on_ground
+            && self.standing
+            && self.velocity.y > 0.0
+            && ((contacts.wall_left && input.held(Action::MoveLeft))
+                || (contacts.wall_right && input.held(Action::MoveRight)));
+        if self.wall_sliding {
+            self.velocity.y = self.velocity.y.min(config.wall_slide_speed);
+        }
+        if on_ground {
+            self.coyote_ticks = config.coyote_time;
+        } else {
+            self.coyote_ticks = self.coyote_ticks.saturating_sub(1);
         }
+        self.on_ground = on_ground;
+        self.head_covered = contacts.head_covered;
+        self.wall_left = contacts.wall_left;
+        self.wall_right = contacts.wall_right;
     }
-    pub fn draw(&self, layer: &mut RenderLayer) {
-        let animation = if !self.standing {
-            AnimationID::PlayerSlide
+    pub fn draw(&self, layer: &mut RenderLayer, alpha: f32) {
+        // blink while invulnerable
+        if !self.is_dead() && self.invulnerable_ticks / 4 % 2 == 1 {
+            return;
+        }
+        let (animation, time) = if let Some(ticks) = self.death_ticks {
+            (AnimationID::PlayerDeath, ticks * 1000 / 60)
+        } else if !self.standing {
+            (AnimationID::PlayerSlide, self.anim_frame)
+        } else if self.wall_sliding {
+            (AnimationID::PlayerWallSlide, self.anim_frame)
+        } else if self.wall_jump_lockout > 0 {
+            (AnimationID::PlayerWallJump, self.anim_frame)
         } else if self.velocity.length() != 0.0 {
-            AnimationID::PlayerSprint
+            (AnimationID::PlayerSprint, self.anim_frame)
         } else {
-            AnimationID::PlayerIdle
+            (AnimationID::PlayerIdle, self.anim_frame)
         };
 
+        let pos = self.last_pos.lerp(self.pos, alpha).floor();
         layer.calls.push(DrawCall::Animation(
             animation,
-            self.anim_frame,
-            self.pos.floor().x - 4.0,
-            self.pos.floor().y - 8.0,
+            time,
+            pos.x - 4.0,
+            pos.y - 8.0,
             Some(DrawTextureParams {
                 flip_x: !self.facing_right,
                 ..Default::default()
             }),
         ));
     }
+    /// Draws the health bar.
+    pub fn draw_ui(&self, layer: &mut RenderLayer) {
+        let (x, y, w, h) = (4.0, 4.0, 48.0, 4.0);
+        layer
+            .calls
+            .push(DrawCall::Rect(x - 1.0, y - 1.0, w + 2.0, h + 2.0, BLACK));
+        layer.calls.push(DrawCall::Rect(x, y, w, h, DARKGRAY));
+        layer.calls.push(DrawCall::Rect(
+            x,
+            y,
+            (w * self.health / self.max_health).ceil(),
+            h,
+            RED,
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::MovementConfig, screens::Map};
+
+    /// Steps the player one tick with `actions` held, as if they were also held the
+    /// tick before.
+    fn step(player: &mut Player, map: &Map, config: &MovementConfig, actions: &[Action]) {
+        step_from(player, map, config, actions, actions);
+    }
+    /// Steps the player one tick with `actions` held, after holding `before` the tick
+    /// before, so anything only in `actions` counts as pressed.
+    fn step_from(
+        player: &mut Player,
+        map: &Map,
+        config: &MovementConfig,
+        actions: &[Action],
+        before: &[Action],
+    ) {
+        let world = World {
+            config,
+            ..World::built_in(map, &[])
+        };
+        let set = |actions: &[Action]| actions.iter().copied().collect();
+        player.update(&world, Input::from_held(set(actions), set(before)));
+    }
+    /// A player standing still on the floor at `pos`.
+    fn standing_at(pos: Vec2) -> Player {
+        let mut player = Player::new();
+        player.pos = pos;
+        player.last_pos = pos;
+        player.on_ground = true;
+        player
+    }
+
+    #[test]
+    fn slides_under_a_one_tile_ceiling() {
+        let map = Map::from_ascii(
+            "
+            ....................
+            ....................
+            ....................
+            ........#######.....
+            ....................
+            ####################
+            ",
+        );
+        let config = MovementConfig::built_in();
+        let mut player = standing_at(Vec2::new(8.0, 32.0));
+        let (tunnel_left, tunnel_right) = (64.0, 120.0);
+        for tick in 0..200 {
+            let actions: &[Action] = match tick {
+                0..10 => &[Action::MoveRight],
+                // let go of slide halfway through, which mustn't stand up into the ceiling
+                10..30 => &[Action::MoveRight, Action::Slide],
+                _ => &[Action::MoveRight],
+            };
+            step(&mut player, &map, &config, actions);
+            assert_eq!(player.pos.y, 32.0, "left the floor on tick {tick}");
+            assert!(player.on_ground, "left the floor on tick {tick}");
+            let hitbox = player.hitbox();
+            if hitbox.right() > tunnel_left && hitbox.left() < tunnel_right {
+                assert!(!player.standing, "stood up in the tunnel on tick {tick}");
+                assert!(
+                    player.head_covered,
+                    "head uncovered in the tunnel on tick {tick}"
+                );
+            }
+        }
+        assert!(player.pos.x > tunnel_right, "stuck at {}", player.pos.x);
+        assert!(player.standing);
+        assert!(!player.head_covered);
+    }
+
+    #[test]
+    fn landing_a_slide_boosts_it() {
+        let map = Map::from_ascii(
+            "
+            ..........................
+            ..........................
+            ..........................
+            ..........................
+            ..........................
+            ##########################
+            ",
+        );
+        let config = MovementConfig::built_in();
+        let mut player = Player::new();
+        player.pos = Vec2::new(8.0, 8.0);
+        player.standing = false;
+        player.velocity.x = 2.0;
+        let mut before = player.velocity;
+        while !player.on_ground {
+            before = player.velocity;
+            step(&mut player, &map, &config, &[Action::Slide]);
+            assert!(player.pos.y <= 32.0, "fell through the floor");
+        }
+        assert_eq!(player.pos.y, 32.0);
+        // the 24 pixel drop lands falling at 5.4, having slowed to about 1.77 sideways
+        assert!((before.y - 5.4).abs() < 1e-4, "fell at {}", before.y);
+        assert!(
+            (player.velocity.x - 3.9214).abs() < 1e-4,
+            "landed going {}",
+            player.velocity.x
+        );
+    }
+
+    #[test]
+    fn soft_slide_landings_have_a_capped_boost() {
+        let map = Map::from_ascii(
+            "
+            ..........
+            ..........
+            ..........
+            ..........
+            ..........
+            ##########
+            ",
+        );
+        let config = MovementConfig::built_in();
+        let mut player = Player::new();
+        // barely falling, half a pixel above the floor
+        player.pos = Vec2::new(8.0, 31.5);
+        player.standing = false;
+        player.velocity = Vec2::new(2.0, 0.01);
+        step(&mut player, &map, &config, &[Action::Slide]);
+        assert!(player.on_ground);
+        assert!(
+            (player.velocity.x - 4.96).abs() < 1e-4,
+            "landed going {}",
+            player.velocity.x
+        );
+    }
+
+    #[test]
+    fn landing_standing_has_no_boost() {
+        let map = Map::from_ascii(
+            "
+            ..........
+            ..........
+            ..........
+            ..........
+            ##########
+            ",
+        );
+        let config = MovementConfig::built_in();
+        let mut player = Player::new();
+        player.pos = Vec2::new(8.0, 8.0);
+        player.velocity.x = 2.0;
+        while !player.on_ground {
+            step(&mut player, &map, &config, &[]);
+        }
+        assert_eq!(player.pos.y, 24.0);
+        assert!(player.velocity.x < 2.0);
+    }
+
+    #[test]
+    fn slides_down_and_jumps_off_walls() {
+        let map = Map::from_ascii(
+            "
+            ..........#
+            ..........#
+            ..........#
+            ..........#
+            ..........#
+            ..........#
+            ..........#
+            ..........#
+            ###########
+            ",
+        );
+        let config = MovementConfig::built_in();
+        // in the air, right up against the wall
+        let mut player = Player::new();
+        player.pos = Vec2::new(72.0, 16.0);
+        player.last_pos = player.pos;
+        for _ in 0..20 {
+            step(&mut player, &map, &config, &[Action::MoveRight]);
+            assert!(player.velocity.y <= config.wall_slide_speed);
+        }
+        assert!(player.wall_sliding && player.wall_right);
+        assert_eq!(player.pos.x, 72.0);
+        assert!(player.pos.y > 24.0, "didn't slide down");
+
+        let held = [Action::MoveRight, Action::Jump];
+        step_from(&mut player, &map, &config, &held, &[Action::MoveRight]);
+        assert!(!player.wall_sliding && !player.facing_right);
+        assert!(player.velocity.x < 0.0 && player.velocity.y < 0.0);
+        let kicked_off = player.pos;
+        // holding towards the wall doesn't cancel the kick
+        for _ in 0..5 {
+            step(&mut player, &map, &config, &held);
+        }
+        assert!(player.pos.x < kicked_off.x - 4.0);
+        assert!(player.pos.y < kicked_off.y);
+    }
+
+    #[test]
+    fn jumps_just_after_running_off_a_ledge() {
+        let map = Map::from_ascii(
+            "
+            ..........
+            ..........
+            ..........
+            #####.....
+            ..........
+            ..........
+            ..........
+            ..........
+            ..........
+            ..........
+            ",
+        );
+        let config = MovementConfig::built_in();
+        // whether pressing jump on the `tick`th tick off the ledge, counting the one
+        // running off it, jumps
+        let jumps_on = |tick: u32| {
+            let mut player = standing_at(Vec2::new(16.0, 16.0));
+            while player.on_ground {
+                step(&mut player, &map, &config, &[Action::MoveRight]);
+            }
+            for _ in 2..tick {
+                step(&mut player, &map, &config, &[Action::MoveRight]);
+            }
+            assert!(!player.on_ground && player.velocity.y > 0.0);
+            let held = [Action::MoveRight, Action::Jump];
+            step_from(&mut player, &map, &config, &held, &[Action::MoveRight]);
+            player.jump_frames == 1
+        };
+        assert!(jumps_on(2));
+        assert!(jumps_on(config.coyote_time));
+        assert!(!jumps_on(config.coyote_time + 1));
+    }
+
+    #[test]
+    fn jumps_pressed_just_before_landing_happen_on_landing() {
+        let map = Map::from_ascii(
+            "
+            ..........
+            ..........
+            ..........
+            ..........
+            ..........
+            ..........
+            ..........
+            ..........
+            ..........
+            ##########
+            ",
+        );
+        let config = MovementConfig::built_in();
+        let dropped = || {
+            let mut player = Player::new();
+            player.pos = Vec2::new(32.0, 8.0);
+            player.last_pos = player.pos;
+            player
+        };
+        let mut player = dropped();
+        let mut touchdown = 0;
+        while !player.on_ground {
+            step(&mut player, &map, &config, &[]);
+            touchdown += 1;
+        }
+        // whether tapping jump `early` ticks before the first tick on the floor jumps
+        // on that tick
+        let jumps_from = |early: u32| {
+            let mut player = dropped();
+            for tick in 0..=touchdown {
+                let actions: &[Action] = if tick == touchdown - early {
+                    &[Action::Jump]
+                } else {
+                    &[]
+                };
+                step_from(&mut player, &map, &config, actions, &[]);
+            }
+            player.jump_frames == 1
+        };
+        assert!(touchdown > config.jump_buffer, "fell too fast to test");
+        assert!(jumps_from(3));
+        assert!(jumps_from(config.jump_buffer - 1));
+        assert!(!jumps_from(config.jump_buffer));
+    }
+
+    #[test]
+    fn hits_knock_back_and_are_ignored_while_invulnerable() {
+        let map = Map::from_ascii(
+            "
+            ..........
+            ..........
+            ..........
+            ##########
+            ",
+        );
+        let config = MovementConfig::built_in();
+        let mut player = standing_at(Vec2::new(32.0, 16.0));
+        let source = Vec2::new(40.0, 16.0);
+        player.damage(30.0, source);
+        assert_eq!(player.health, 70.0);
+        // away from the source, and up
+        assert!(player.velocity.x < 0.0 && player.velocity.y < 0.0);
+
+        player.damage(30.0, source);
+        assert_eq!(player.health, 70.0);
+        for _ in 0..INVULNERABLE_TICKS {
+            step(&mut player, &map, &config, &[]);
+        }
+        player.damage(30.0, source);
+        assert_eq!(player.health, 40.0);
+    }
+
+    #[test]
+    fn dies_when_out_of_health_until_respawned() {
+        let map = Map::from_ascii(
+            "
+            ..........
+            ..........
+            ..........
+            ##########
+            ",
+        );
+        let config = MovementConfig::built_in();
+        let mut player = standing_at(Vec2::new(32.0, 16.0));
+        player.damage(60.0, Vec2::ZERO);
+        player.invulnerable_ticks = 0;
+        player.damage(60.0, Vec2::ZERO);
+        assert!(player.is_dead());
+        assert_eq!(player.health, 0.0);
+
+        for _ in 1..DEATH_TICKS {
+            step(&mut player, &map, &config, &[]);
+            assert!(!player.should_respawn());
+        }
+        step(&mut player, &map, &config, &[]);
+        assert!(player.should_respawn());
+
+        let spawn = Vec2::new(8.0, 16.0);
+        player.respawn(spawn);
+        assert!(!player.is_dead());
+        assert_eq!(player.pos, spawn);
+        assert_eq!(player.health, player.max_health);
+    }
+
+    #[test]
+    fn kill_ignores_health_and_invulnerability() {
+        let mut player = standing_at(Vec2::new(32.0, 16.0));
+        player.invulnerable_ticks = INVULNERABLE_TICKS;
+        player.kill();
+        assert!(player.is_dead());
+        assert_eq!(player.health, 0.0);
+    }
 }
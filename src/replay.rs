@@ -0,0 +1,212 @@
+//! Recordings of play sessions, as the controls held on every tick.
+//!
+//! The simulation is deterministic, so playing back the same inputs from the same
+//! screen, spawn and movement config reproduces a run exactly.
+
+use std::{fmt, fs, io, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::MovementConfig,
+    input::{ActionSet, Input},
+    screens::ScreenLink,
+};
+
+/// Version of the game, which replays must match to play back the same.
+pub const GAME_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+pub struct Replay {
+    /// Version of the game the replay was recorded with
+    pub version: String,
+    /// Where the run started
+    pub start: ScreenLink,
+    /// Movement config the run was played with
+    pub config: MovementConfig,
+    /// Actions held on each tick
+    pub inputs: Vec<ActionSet>,
+}
+
+/// How a replay is saved. Inputs are run-length encoded, as `ticks*actions` pairs.
+#[derive(Serialize, Deserialize)]
+struct ReplayFile {
+    version: String,
+    screen: String,
+    spawn: String,
+    inputs: String,
+    config: MovementConfig,
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(io::Error),
+    Toml(toml::de::Error),
+    /// The replay was recorded with a different version of the game
+    WrongVersion(String),
+    /// A run of inputs that isn't written as `ticks*actions`
+    InvalidInputs(String),
+}
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Io(e) => write!(f, "{e}"),
+            ReplayError::Toml(e) => write!(f, "{e}"),
+            ReplayError::WrongVersion(version) => write!(
+                f,
+                "recorded with nora v{version}, but this is v{GAME_VERSION}"
+            ),
+            ReplayError::InvalidInputs(run) => write!(f, "invalid inputs \"{run}\""),
+        }
+    }
+}
+impl std::error::Error for ReplayError {}
+
+impl Replay {
+    /// Starts an empty recording of a run from `start`.
+    pub fn new(start: ScreenLink, config: &MovementConfig) -> Self {
+        Self {
+            version: GAME_VERSION.to_string(),
+            start,
+            config: config.clone(),
+            inputs: Vec::new(),
+        }
+    }
+    /// Reads a replay, refusing ones recorded with another version of the game.
+    pub fn parse(text: &str) -> Result<Self, ReplayError> {
+        let file: ReplayFile = toml::from_str(text).map_err(ReplayError::Toml)?;
+        if file.version != GAME_VERSION {
+            return Err(ReplayError::WrongVersion(file.version));
+        }
+        let mut inputs = Vec::new();
+        for run in file.inputs.split_whitespace() {
+            let invalid = || ReplayError::InvalidInputs(run.to_string());
+            let (ticks, actions) = run.split_once('*').ok_or_else(invalid)?;
+            let ticks: usize = ticks.parse().map_err(|_| invalid())?;
+            let actions = ActionSet(actions.parse().map_err(|_| invalid())?);
+            inputs.extend(std::iter::repeat_n(actions, ticks));
+        }
+        Ok(Self {
+            version: file.version,
+            start: ScreenLink::new(&file.screen, &file.spawn),
+            config: file.config,
+            inputs,
+        })
+    }
+    pub fn to_toml(&self) -> String {
+        let mut runs: Vec<(usize, ActionSet)> = Vec::new();
+        for actions in &self.inputs {
+            match runs.last_mut() {
+                Some((ticks, last)) if last == actions => *ticks += 1,
+                _ => runs.push((1, *actions)),
+            }
+        }
+        let inputs = runs
+            .iter()
+            .map(|(ticks, actions)| format!("{ticks}*{}", actions.0))
+            .collect::<Vec<_>>()
+            .join(" ");
+        toml::to_string(&ReplayFile {
+            version: self.version.clone(),
+            screen: self.start.screen.clone(),
+            spawn: self.start.spawn.clone(),
+            inputs,
+            config: self.config.clone(),
+        })
+        .expect("replays should always serialize")
+    }
+    pub fn load(path: &Path) -> Result<Self, ReplayError> {
+        Self::parse(&fs::read_to_string(path).map_err(ReplayError::Io)?)
+    }
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.to_toml())
+    }
+}
+
+/// Feeds a replay's inputs back, one tick at a time.
+pub struct ReplayPlayer {
+    pub replay: Replay,
+    /// Index of the next tick's input
+    tick: usize,
+    /// What was held on the last tick
+    last: ActionSet,
+}
+impl ReplayPlayer {
+    pub fn new(replay: Replay) -> Self {
+        Self {
+            replay,
+            tick: 0,
+            last: ActionSet::default(),
+        }
+    }
+}
+impl Iterator for ReplayPlayer {
+    type Item = Input;
+
+    /// The next tick's input, or `None` once the replay is over.
+    fn next(&mut self) -> Option<Input> {
+        let held = *self.replay.inputs.get(self.tick)?;
+        self.tick += 1;
+        let input = Input::from_held(held, self.last);
+        self.last = held;
+        Some(input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::{Action, InputSampler};
+
+    fn held(actions: &[Action]) -> ActionSet {
+        actions.iter().copied().collect()
+    }
+
+    #[test]
+    fn plays_back_what_was_recorded() {
+        use Action::*;
+        let config = MovementConfig::built_in();
+        let mut replay = Replay::new(ScreenLink::new("1", "entrance"), &config);
+        // recording starts with a fresh sampler, as it does after restarting a level, so
+        // keys already held on the first tick count as pressed both live and played back
+        let mut sampler = InputSampler::new();
+        let recorded: Vec<Input> = [
+            &[MoveRight][..],
+            &[MoveRight, Jump],
+            &[MoveRight, Jump],
+            &[],
+            &[Jump],
+            &[Jump],
+            &[MoveLeft, Slide],
+        ]
+        .into_iter()
+        .map(|actions| {
+            let input = sampler.advance(held(actions));
+            replay.inputs.push(input.held);
+            input
+        })
+        .collect();
+
+        let path = std::env::temp_dir().join(format!("nora-replay-{}.toml", std::process::id()));
+        replay.save(&path).unwrap();
+        let loaded = Replay::load(&path);
+        fs::remove_file(&path).unwrap();
+        let loaded = loaded.unwrap();
+
+        assert_eq!(loaded.start, replay.start);
+        let played: Vec<Input> = ReplayPlayer::new(loaded).collect();
+        assert_eq!(played, recorded);
+    }
+
+    #[test]
+    fn rejects_other_versions() {
+        let config = MovementConfig::built_in();
+        let replay = Replay::new(ScreenLink::new("1", "entrance"), &config);
+        let text = replay
+            .to_toml()
+            .replace(&format!("\"{GAME_VERSION}\""), "\"0.0.1\"");
+        assert!(matches!(
+            Replay::parse(&text),
+            Err(ReplayError::WrongVersion(version)) if version == "0.0.1"
+        ));
+    }
+}
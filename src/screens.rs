@@ -1,142 +1,394 @@
-use enum_iterator::Sequence;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    str::FromStr,
+};
+
 use macroquad::prelude::*;
 use struct_iterable::Iterable;
 
 use crate::{
-    assets::AnimationID,
-    entity::{HumanoidEnemy, NonPlayerEntity},
+    camera::FollowCamera,
+    config::MovementConfig,
+    debug,
+    entity::{self, NonPlayerEntity, World},
     graphics::{DrawCall, RenderLayers},
+    input::Input,
+    nav::NavGraph,
     player::Player,
+    tmx::{Object, Properties, Tileset, TmxError, TmxErrorKind, TmxMap},
     utils::*,
 };
 
 pub struct ScreenUpdateContext<'a> {
     pub player: &'a mut Player,
     pub render_layers: &'a mut RenderLayers,
+    pub config: &'a MovementConfig,
+    /// Whether to draw the debug overlay
+    pub debug_overlay: bool,
+    /// The controls for this tick
+    pub input: Input,
 }
 pub enum ScreenUpdateResult {
     /// Does nothing special
     Pass,
     /// Requests change to a different screen
-    ChangeScreen(ScreenID, usize),
+    ChangeScreen(ScreenLink),
 }
 
 #[expect(unused_variables)]
 pub trait Screen {
-    fn on_load(&mut self, ctx: ScreenUpdateContext, spawn_index: usize) {}
+    fn on_load(&mut self, ctx: ScreenUpdateContext, spawn: &str) {}
+    /// Called when the game is paused, after which the screen stops updating until resumed.
+    fn on_pause(&mut self, ctx: ScreenUpdateContext) {}
+    fn on_resume(&mut self, ctx: ScreenUpdateContext) {}
     fn update(&mut self, ctx: ScreenUpdateContext) -> ScreenUpdateResult {
         ScreenUpdateResult::Pass
     }
-    fn draw(&mut self, ctx: ScreenUpdateContext) {}
+    /// `alpha` is how far between the previous and current tick to draw, from 0 to 1.
+    fn draw(&mut self, ctx: ScreenUpdateContext, alpha: f32) {}
+    /// Whether the player can be spawned at the named spawn point.
+    fn has_spawn(&self, spawn: &str) -> bool {
+        true
+    }
+    /// Every screen this one can lead to.
+    fn exits(&self) -> Vec<&ScreenLink> {
+        Vec::new()
+    }
+    /// Checks the screen is set up correctly, given every other screen.
+    fn validate(&self, screens: &ScreenRegistry) -> Vec<ScreenProblem> {
+        Vec::new()
+    }
+}
+
+/// Something wrong with how a screen is linked up, found by [validate_screens].
+#[derive(Debug)]
+pub enum ScreenProblem {
+    /// The screen has tiles for an exit that doesn't lead anywhere
+    UnlinkedExit(usize),
+    /// A link, either the start screen or an exit, targets a screen that doesn't exist
+    UnknownScreen(Option<usize>, ScreenLink),
+    /// A link targets a screen without the requested spawn
+    MissingSpawn(Option<usize>, ScreenLink),
+    /// The screen has reset tiles, but no spawn 0 to reset to
+    ResetWithoutSpawn,
+    /// A checkpoint, by tile position, where the player would respawn inside a wall
+    BlockedCheckpoint(usize, usize),
+}
+impl fmt::Display for ScreenProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let source = |exit: &Option<usize>| match exit {
+            Some(exit) => format!("exit #{exit}"),
+            None => "start".to_string(),
+        };
+        match self {
+            ScreenProblem::UnlinkedExit(exit) => {
+                write!(f, "exit #{exit} has tiles but isn't linked to a screen")
+            }
+            ScreenProblem::UnknownScreen(exit, link) => write!(
+                f,
+                "{} leads to screen \"{}\", which doesn't exist",
+                source(exit),
+                link.screen
+            ),
+            ScreenProblem::MissingSpawn(exit, link) => write!(
+                f,
+                "{} leads to spawn \"{}\", which screen \"{}\" doesn't have",
+                source(exit),
+                link.spawn,
+                link.screen
+            ),
+            ScreenProblem::ResetWithoutSpawn => write!(f, "has reset tiles but no spawn 0"),
+            ScreenProblem::BlockedCheckpoint(x, y) => {
+                write!(f, "checkpoint at {x},{y} is inside a wall")
+            }
+        }
+    }
 }
 
-#[derive(Clone, Debug, Copy, PartialEq, Eq, Hash, Sequence)]
-pub enum ScreenID {
-    Test,
-    Level1,
-    Level2,
-    Level3,
-    Level4,
-    Level5,
-    Level6,
-    Level7,
-    Level8,
+/// Checks a link can be taken, returning the problem if not.
+fn validate_link(
+    screens: &ScreenRegistry,
+    exit: Option<usize>,
+    link: &ScreenLink,
+) -> Option<ScreenProblem> {
+    match screens.get(&link.screen) {
+        None => Some(ScreenProblem::UnknownScreen(exit, link.clone())),
+        Some(screen) if !screen.has_spawn(&link.spawn) => {
+            Some(ScreenProblem::MissingSpawn(exit, link.clone()))
+        }
+        Some(_) => None,
+    }
 }
-impl From<ScreenID> for usize {
-    fn from(val: ScreenID) -> Self {
-        val as usize
+
+/// Finds the names of screens that can't be reached from the start by taking exits.
+pub fn unreachable_screens(screens: &ScreenRegistry, start: &ScreenLink) -> Vec<String> {
+    let mut reached = HashSet::new();
+    let mut queue = vec![start.screen.as_str()];
+    while let Some(name) = queue.pop() {
+        if !reached.insert(name) {
+            continue;
+        }
+        if let Some(screen) = screens.get(name) {
+            queue.extend(screen.exits().into_iter().map(|link| link.screen.as_str()));
+        }
+    }
+    let mut unreachable: Vec<String> = screens
+        .keys()
+        .filter(|name| !reached.contains(name.as_str()))
+        .cloned()
+        .collect();
+    unreachable.sort();
+    unreachable
+}
+
+/// Checks every screen and the start link, returning all problems found, by screen name.
+pub fn validate_screens(
+    screens: &ScreenRegistry,
+    start: &ScreenLink,
+) -> Vec<(String, ScreenProblem)> {
+    let mut problems: Vec<(String, ScreenProblem)> = Vec::new();
+    if let Some(problem) = validate_link(screens, None, start) {
+        problems.push((start.screen.clone(), problem));
+    }
+    let mut names: Vec<&String> = screens.keys().collect();
+    names.sort();
+    for name in names {
+        for problem in screens[name].validate(screens) {
+            problems.push((name.clone(), problem));
+        }
+    }
+    problems
+}
+
+/// A spawn point in a screen, both given by name.
+///
+/// Written as `screen:spawn` in map properties.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScreenLink {
+    pub screen: String,
+    pub spawn: String,
+}
+impl ScreenLink {
+    pub fn new(screen: &str, spawn: &str) -> Self {
+        Self {
+            screen: screen.to_string(),
+            spawn: spawn.to_string(),
+        }
+    }
+}
+impl FromStr for ScreenLink {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (screen, spawn) = s.split_once(':').ok_or(())?;
+        Ok(Self::new(screen.trim(), spawn.trim()))
     }
 }
 
-pub fn create_screen_registry() -> Registry<ScreenID, Box<dyn Screen>> {
-    let f: Box<dyn Fn(ScreenID) -> Box<dyn Screen>> = Box::new(|id| match id {
-        ScreenID::Test => Box::new(TilemapScreen::new(
-            include_str!("../assets/screens/test.tmx"),
-            vec![Box::new(HumanoidEnemy::new(
-                Vec2::new(25.0, 17.0) * 8.0,
-                AnimationID::PlayerSprint,
-                0.4,
-            ))],
-            vec![(ScreenID::Level1, 1), (ScreenID::Level1, 0)],
-        )),
-        ScreenID::Level1 => Box::new(TilemapScreen::new(
-            include_str!("../assets/screens/1.tmx"),
-            vec![],
-            vec![(ScreenID::Level2, 0)],
-        )),
-        ScreenID::Level2 => Box::new(TilemapScreen::new(
-            include_str!("../assets/screens/2.tmx"),
-            vec![],
-            vec![(ScreenID::Level1, 1), (ScreenID::Level3, 0)],
-        )),
-        ScreenID::Level3 => Box::new(TilemapScreen::new(
-            include_str!("../assets/screens/3.tmx"),
-            vec![],
-            vec![(ScreenID::Level2, 1), (ScreenID::Level4, 0)],
-        )),
-        ScreenID::Level4 => Box::new(TilemapScreen::new(
-            include_str!("../assets/screens/4.tmx"),
-            vec![],
-            vec![(ScreenID::Level3, 1), (ScreenID::Level5, 0)],
-        )),
-        ScreenID::Level5 => Box::new(TilemapScreen::new(
-            include_str!("../assets/screens/5.tmx"),
-            vec![],
-            vec![(ScreenID::Level4, 1), (ScreenID::Level6, 0)],
-        )),
-        ScreenID::Level6 => Box::new(TilemapScreen::new(
-            include_str!("../assets/screens/6.tmx"),
-            vec![],
-            vec![(ScreenID::Level5, 1), (ScreenID::Level7, 0)],
-        )),
-        ScreenID::Level7 => Box::new(TilemapScreen::new(
-            include_str!("../assets/screens/7.tmx"),
-            vec![],
-            vec![(ScreenID::Level6, 1), (ScreenID::Level8, 0)],
-        )),
-        ScreenID::Level8 => Box::new(WinScreen::new(include_str!("../assets/screens/win.tmx"))),
-    });
-    Registry::new(f)
+/// Where the game starts.
+pub fn start_screen() -> ScreenLink {
+    ScreenLink::new("1", "entrance")
+}
+
+/// Pairs a screen file's name with its contents, so load errors can name the file.
+macro_rules! screen_file {
+    ($name:literal) => {
+        ($name, include_str!(concat!("../assets/screens/", $name)))
+    };
+}
+
+/// Every screen in the game. Each is registered under its file name, without the extension.
+///
+/// How screens link together is set by their map properties:
+/// - `exit0` to `exit3`: the [ScreenLink] taken by exit tiles 4 to 7
+/// - `spawn0` to `spawn3`: names for spawn tiles 8 to 11, which otherwise go by their number
+/// - `kind`: set to `win` for the win screen
+///
+/// Besides exits and spawns, the special layer has jump hints for enemies in tiles 1 to 3,
+/// tile 12 to send the player back to spawn 0, and tile 13 for checkpoints. Once the
+/// player touches a checkpoint, they respawn there after dying, instead of where they
+/// entered the screen.
+pub const SCREEN_FILES: [(&str, &str); 9] = [
+    screen_file!("test.tmx"),
+    screen_file!("1.tmx"),
+    screen_file!("2.tmx"),
+    screen_file!("3.tmx"),
+    screen_file!("4.tmx"),
+    screen_file!("5.tmx"),
+    screen_file!("6.tmx"),
+    screen_file!("7.tmx"),
+    screen_file!("win.tmx"),
+];
+
+pub type ScreenRegistry = HashMap<String, Box<dyn Screen>>;
+
+pub fn create_screen_registry() -> Result<ScreenRegistry, TmxError> {
+    let mut screens = ScreenRegistry::new();
+    for file in SCREEN_FILES {
+        screens.insert(screen_name(file.0).to_string(), load_screen(file)?);
+    }
+    Ok(screens)
+}
+/// The name a screen file is registered under.
+fn screen_name(file: &str) -> &str {
+    file.trim_end_matches(".tmx")
+}
+/// Loads a screen from its file, as the kind of screen its map properties ask for.
+pub fn load_screen(file: (&str, &str)) -> Result<Box<dyn Screen>, TmxError> {
+    let map = Map::from_file(file)?;
+    Ok(match map.properties.get("kind") {
+        Some("win") => Box::new(WinScreen::new(map)),
+        _ => Box::new(TilemapScreen::new(file.0, map)?),
+    })
+}
+/// Replaces the named screen with a fresh copy from its file, undoing everything that
+/// happened in it, like enemies being crushed.
+pub fn reset_screen(screens: &mut ScreenRegistry, name: &str) -> Result<(), TmxError> {
+    if let Some(file) = SCREEN_FILES
+        .into_iter()
+        .find(|file| screen_name(file.0) == name)
+    {
+        screens.insert(name.to_string(), load_screen(file)?);
+    }
+    Ok(())
 }
 
 type Tiles = Vec<usize>;
 
+/// The tileset every map uses, whose tile properties set how collision tiles behave.
+///
+/// A tile with a `slope` property of `left,right` is a ramp, whose floor is `left` and
+/// `right` pixels high at its edges, like `0,8` for a 45° ramp up to the right.
+/// A tile with a `platform` property of `true` is a one-way platform.
+const TILESET_FILE: (&str, &str) = ("tileset.tsx", include_str!("../assets/tileset.tsx"));
+
+/// How a tile in the collision layer behaves.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CollisionTile {
+    Empty,
+    Solid,
+    /// A ramp, with the floor's height in pixels at the left and right edge of the tile
+    Slope(f32, f32),
+    /// A one-way platform, which can be jumped up through and dropped down through
+    Platform,
+}
+impl CollisionTile {
+    /// Height of the floor in pixels, measured from the bottom of the tile,
+    /// at `x` pixels from the tile's left edge.
+    pub fn floor_height(&self, x: f32) -> Option<f32> {
+        match self {
+            CollisionTile::Empty => None,
+            CollisionTile::Solid | CollisionTile::Platform => Some(8.0),
+            CollisionTile::Slope(left, right) => {
+                Some(left + (right - left) * (x / 8.0).clamp(0.0, 1.0))
+            }
+        }
+    }
+    /// Reads a tile's kind from its properties in the tileset.
+    ///
+    /// On failure, returns the name of the invalid property.
+    fn from_properties(properties: Option<&Properties>) -> Result<Self, &'static str> {
+        let Some(properties) = properties else {
+            return Ok(Self::Solid);
+        };
+        if let Some(value) = properties.get("slope") {
+            return Self::parse_slope(value).ok_or("slope");
+        }
+        match properties.get("platform").map(str::parse) {
+            Some(Ok(true)) => Ok(Self::Platform),
+            Some(Ok(false)) | None => Ok(Self::Solid),
+            Some(Err(_)) => Err("platform"),
+        }
+    }
+    /// Parses a tileset's `slope` property.
+    fn parse_slope(value: &str) -> Option<Self> {
+        let (left, right) = value.split_once(',')?;
+        let left: f32 = left.trim().parse().ok()?;
+        let right: f32 = right.trim().parse().ok()?;
+        let range = 0.0..=8.0;
+        (range.contains(&left) && range.contains(&right)).then_some(Self::Slope(left, right))
+    }
+}
+
 #[derive(Iterable)]
 pub struct Map {
+    /// Width in tiles
+    pub width: usize,
+    /// Height in tiles
+    pub height: usize,
     background: Tiles,
     walls: Tiles,
     collision: Tiles,
     detail: Tiles,
     detail2: Tiles,
     special: Tiles,
+    /// How each tile of the collision layer behaves
+    collision_tiles: Vec<CollisionTile>,
+    /// Objects from every object layer
+    pub objects: Vec<Object>,
+    pub properties: Properties,
 }
 impl Map {
-    pub fn get_collision_tile(&self, x: usize, y: usize) -> usize {
-        if x >= 48 {
-            return 1;
+    pub fn get_collision_tile(&self, x: usize, y: usize) -> CollisionTile {
+        if x >= self.width {
+            return CollisionTile::Solid;
         }
-        if y >= 27 {
-            return 1;
+        if y >= self.height {
+            return CollisionTile::Solid;
+        }
+        self.collision_tiles[x + y * self.width]
+    }
+    /// Collision tile at signed tile coordinates. Everything left of the map is solid,
+    /// and everything above it is empty.
+    pub fn collision_tile(&self, x: i32, y: i32) -> CollisionTile {
+        if x < 0 {
+            CollisionTile::Solid
+        } else if y < 0 {
+            CollisionTile::Empty
+        } else {
+            self.get_collision_tile(x as usize, y as usize)
         }
-        self.collision[x + y * 48]
     }
     pub fn get_special_tile(&self, x: usize, y: usize) -> usize {
-        if x >= 48 {
+        if x >= self.width {
             return 0;
         }
-        if y >= 27 {
+        if y >= self.height {
             return 0;
         }
-        self.special[x + y * 48]
+        self.special[x + y * self.width]
+    }
+    pub fn has_special_tile(&self, tile: usize) -> bool {
+        self.special.contains(&tile)
+    }
+    /// Positions of every special tile of the given kind.
+    pub fn special_tiles(&self, tile: usize) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.special
+            .iter()
+            .enumerate()
+            .filter(move |(_, t)| **t == tile)
+            .map(|(i, _)| (i % self.width, i / self.width))
     }
     pub fn find_special_tile(&self, tile_index: usize) -> Option<(usize, usize)> {
         for (i, tile) in self.special.iter().enumerate() {
             if *tile == tile_index + 1 {
-                return Some((i % 48, i / 48));
+                return Some((i % self.width, i / self.width));
             }
         }
         None
     }
+    /// Every tile layer, by name.
+    pub fn tile_layers(&self) -> [(&'static str, &Tiles); 6] {
+        [
+            ("Background", &self.background),
+            ("Walls", &self.walls),
+            ("Collision", &self.collision),
+            ("Detail", &self.detail),
+            ("Detail2", &self.detail2),
+            ("Special", &self.special),
+        ]
+    }
     fn layers(&self) -> [&Tiles; 5] {
         [
             &self.background,
@@ -146,70 +398,176 @@ impl Map {
             &self.detail2,
         ]
     }
-    fn draw(&self, ctx: &mut ScreenUpdateContext) {
+    /// Size in world pixels
+    pub fn bounds(&self) -> Rect {
+        Rect::new(0.0, 0.0, self.width as f32 * 8.0, self.height as f32 * 8.0)
+    }
+    /// Draws the tiles within `view` onto the world layer.
+    fn draw(&self, ctx: &mut ScreenUpdateContext, view: Rect) {
+        let min_x = (view.x / 8.0).floor().max(0.0) as usize;
+        let min_y = (view.y / 8.0).floor().max(0.0) as usize;
+        let max_x = (((view.x + view.w) / 8.0).ceil().max(0.0) as usize).min(self.width);
+        let max_y = (((view.y + view.h) / 8.0).ceil().max(0.0) as usize).min(self.height);
+
+        ctx.render_layers
+            .world
+            .calls
+            .push(DrawCall::Clear(BLACK.with_alpha(0.0)));
         for layer in self.layers().iter() {
-            for (index, tile) in layer.iter().enumerate() {
-                if let Some(tile) = tile.checked_sub(1) {
-                    let x = (index % 48) as f32;
-                    let y = (index / 48) as f32;
-
-                    ctx.render_layers.world.calls.push(DrawCall::Tileset(
-                        (tile % 64) as f32,
-                        (tile / 64) as f32,
-                        x * 8.0,
-                        y * 8.0,
-                    ));
+            for y in min_y..max_y {
+                for x in min_x..max_x {
+                    if let Some(tile) = layer[x + y * self.width].checked_sub(1) {
+                        ctx.render_layers.world.calls.push(DrawCall::Tileset(
+                            (tile % TILESET_COLUMNS) as f32,
+                            (tile / TILESET_COLUMNS) as f32,
+                            x as f32 * 8.0,
+                            y as f32 * 8.0,
+                        ));
+                    }
                 }
             }
         }
     }
-    fn from_file(data: &str) -> Self {
+    pub fn from_file((file, data): (&str, &str)) -> Result<Self, TmxError> {
+        let tmx = TmxMap::parse(file, data)?;
+        let layer = |name| -> Result<Tiles, TmxError> {
+            let layer = tmx.tile_layer(file, name)?;
+            if (layer.width, layer.height) != (tmx.width, tmx.height) {
+                return Err(TmxError {
+                    file: file.to_string(),
+                    layer: Some(name.to_string()),
+                    kind: TmxErrorKind::WrongLayerSize {
+                        expected: (tmx.width, tmx.height),
+                        found: (layer.width, layer.height),
+                    },
+                });
+            }
+            Ok(layer.tiles.clone())
+        };
+
+        let tileset = Tileset::parse(TILESET_FILE.0, TILESET_FILE.1)?;
+        let first_gid = tmx
+            .tilesets
+            .iter()
+            .find(|t| {
+                t.source
+                    .as_ref()
+                    .is_some_and(|s| s.ends_with(TILESET_FILE.0))
+            })
+            .map(|t| t.first_gid as usize);
+        let collision = layer("Collision")?;
+        let collision_tiles = collision
+            .iter()
+            .map(|&gid| {
+                if gid == 0 {
+                    return Ok(CollisionTile::Empty);
+                }
+                let id = first_gid.and_then(|first| gid.checked_sub(first));
+                let properties = id.and_then(|id| tileset.tiles.get(&(id as u32)));
+                CollisionTile::from_properties(properties).map_err(|name| TmxError {
+                    file: TILESET_FILE.0.to_string(),
+                    layer: None,
+                    kind: TmxErrorKind::InvalidTileProperty {
+                        tile: id.unwrap_or_default() as u32,
+                        name: name.to_string(),
+                        value: properties
+                            .and_then(|p| p.get(name))
+                            .unwrap_or_default()
+                            .to_string(),
+                    },
+                })
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            width: tmx.width,
+            height: tmx.height,
+            background: layer("Background")?,
+            walls: layer("Walls")?,
+            collision,
+            collision_tiles,
+            detail: layer("Detail")?,
+            detail2: layer("Detail2")?,
+            special: layer("Special")?,
+            properties: tmx.properties,
+            objects: tmx
+                .object_groups
+                .into_iter()
+                .flat_map(|group| group.objects)
+                .collect(),
+        })
+    }
+}
+#[cfg(test)]
+impl Map {
+    /// Builds a map drawn one character per tile: `#` is solid, `-` a one-way platform,
+    /// `/` and `\` 45° ramps, and anything else empty. `S` marks spawn 0, `E` exit 0
+    /// and `C` a checkpoint, in empty tiles.
+    ///
+    /// Blank lines and indentation are ignored, so maps can be written in place.
+    pub(crate) fn from_ascii(text: &str) -> Self {
+        let rows: Vec<&str> = text
+            .lines()
+            .map(str::trim)
+            .filter(|row| !row.is_empty())
+            .collect();
+        let width = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+        let height = rows.len();
+        let mut collision_tiles = vec![CollisionTile::Empty; width * height];
+        let mut special = vec![0; width * height];
+        for (y, row) in rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                special[x + y * width] = match c {
+                    'E' => 4,
+                    'S' => 8,
+                    'C' => 13,
+                    _ => 0,
+                };
+                collision_tiles[x + y * width] = match c {
+                    '#' => CollisionTile::Solid,
+                    '-' => CollisionTile::Platform,
+                    '/' => CollisionTile::Slope(0.0, 8.0),
+                    '\\' => CollisionTile::Slope(8.0, 0.0),
+                    _ => CollisionTile::Empty,
+                };
+            }
+        }
+        let collision = collision_tiles
+            .iter()
+            .map(|tile| (*tile != CollisionTile::Empty) as usize)
+            .collect();
+        let empty = vec![0; width * height];
         Self {
-            background: parse_tilemap_layer(data, "Background"),
-            walls: parse_tilemap_layer(data, "Walls"),
-            collision: parse_tilemap_layer(data, "Collision"),
-            detail: parse_tilemap_layer(data, "Detail"),
-            detail2: parse_tilemap_layer(data, "Detail2"),
-            special: parse_tilemap_layer(data, "Special"),
+            width,
+            height,
+            background: empty.clone(),
+            walls: empty.clone(),
+            collision,
+            collision_tiles,
+            detail: empty.clone(),
+            detail2: empty,
+            special,
+            objects: Vec::new(),
+            properties: Properties::default(),
         }
     }
 }
-fn parse_tilemap_layer(xml: &str, layer_name: &str) -> Tiles {
-    let pattern = format!("name=\"{layer_name}\" ");
-    let xml = xml
-        .split_once(&pattern)
-        .unwrap()
-        .1
-        .split_once("<data encoding=\"csv\">")
-        .unwrap()
-        .1
-        .split_once("</data>")
-        .unwrap()
-        .0;
-    let split = xml.split(',');
-    let mut data: Tiles = Vec::new();
-    for tile in split {
-        let tile = tile.trim().parse::<usize>().unwrap();
-        data.push(tile);
-    }
-    data
-}
 
 struct WinScreen {
     map: Map,
 }
 impl WinScreen {
-    fn new(file: &str) -> Self {
-        Self {
-            map: Map::from_file(file),
-        }
+    fn new(map: Map) -> Self {
+        Self { map }
     }
 }
 impl Screen for WinScreen {
-    fn on_load(&mut self, mut ctx: ScreenUpdateContext, spawn_index: usize) {
-        self.map.draw(&mut ctx);
+    fn on_load(&mut self, mut ctx: ScreenUpdateContext, _spawn: &str) {
+        let view = FollowCamera::new(self.map.bounds()).view(1.0);
+        ctx.render_layers.set_view(view);
+        self.map.draw(&mut ctx, view);
     }
-    fn draw(&mut self, ctx: ScreenUpdateContext) {
+    fn draw(&mut self, ctx: ScreenUpdateContext, _alpha: f32) {
         for layer in ctx.render_layers.get_redrawn() {
             layer.calls.push(DrawCall::Clear(BLACK.with_alpha(0.0)));
         }
@@ -218,63 +576,355 @@ impl Screen for WinScreen {
 struct TilemapScreen {
     map: Map,
     entities: Vec<Box<dyn NonPlayerEntity>>,
-    linked_screens: Vec<(ScreenID, usize)>,
+    /// Where humanoids can go on `map`, built when the screen first loads if any entity
+    /// navigates
+    nav: Option<NavGraph>,
+    /// Where each exit tile leads, if anywhere
+    exits: [Option<ScreenLink>; 4],
+    /// Names of each spawn tile
+    spawns: [String; 4],
+    camera: FollowCamera,
+    /// Where the player respawns after dying: the spawn they entered from, or the last checkpoint
+    respawn_point: Vec2,
+    /// The view the world layer was last drawn for
+    drawn_view: Option<Rect>,
 }
 impl TilemapScreen {
-    fn new(
-        file: &str,
-        entities: Vec<Box<dyn NonPlayerEntity>>,
-        linked_screens: Vec<(ScreenID, usize)>,
-    ) -> Self {
-        Self {
-            map: Map::from_file(file),
-            entities,
-            linked_screens,
+    fn new(file: &str, map: Map) -> Result<Self, TmxError> {
+        let error = |kind| TmxError {
+            file: file.to_string(),
+            layer: None,
+            kind,
+        };
+        // untyped objects are left for other uses, like marking paths
+        let entities = map
+            .objects
+            .iter()
+            .filter(|object| !object.class.is_empty())
+            .map(entity::from_object)
+            .collect::<Result<_, _>>()
+            .map_err(error)?;
+
+        let mut exits = [const { None }; 4];
+        for (i, exit) in exits.iter_mut().enumerate() {
+            let name = format!("exit{i}");
+            if let Some(value) = map.properties.get(&name) {
+                *exit = Some(value.parse().map_err(|_| {
+                    error(TmxErrorKind::InvalidMapProperty {
+                        name,
+                        value: value.to_string(),
+                    })
+                })?);
+            }
         }
+        let spawns = std::array::from_fn(|i| {
+            map.properties
+                .get(&format!("spawn{i}"))
+                .map(str::to_string)
+                .unwrap_or_else(|| i.to_string())
+        });
+
+        Ok(Self {
+            camera: FollowCamera::new(map.bounds()),
+            respawn_point: Vec2::ZERO,
+            drawn_view: None,
+            nav: None,
+            map,
+            entities,
+            exits,
+            spawns,
+        })
+    }
+    fn spawn_index(&self, spawn: &str) -> Option<usize> {
+        self.spawns.iter().position(|name| name == spawn)
     }
 }
 impl Screen for TilemapScreen {
-    fn on_load(&mut self, mut ctx: ScreenUpdateContext, spawn_index: usize) {
-        self.map.draw(&mut ctx);
-        if let Some((x, y)) = self.map.find_special_tile(7 + spawn_index) {
+    fn on_load(&mut self, ctx: ScreenUpdateContext, spawn: &str) {
+        if let Some(index) = self.spawn_index(spawn)
+            && let Some((x, y)) = self.map.find_special_tile(7 + index)
+        {
             ctx.player.pos = Vec2::new(x as f32 * 8.0, y as f32 * 8.0);
+            ctx.player.last_pos = ctx.player.pos;
         }
+        self.respawn_point = ctx.player.pos;
+        if self.nav.is_none() && self.entities.iter().any(|entity| entity.navigates()) {
+            self.nav = Some(NavGraph::build(
+                &self.map,
+                &entity::humanoid_arc(ctx.config),
+            ));
+        }
+        self.camera.snap(ctx.player.pos, ctx.player.facing_right);
+        self.drawn_view = None;
+    }
+    fn has_spawn(&self, spawn: &str) -> bool {
+        self.spawn_index(spawn)
+            .is_some_and(|index| self.map.find_special_tile(7 + index).is_some())
+    }
+    fn exits(&self) -> Vec<&ScreenLink> {
+        self.exits.iter().flatten().collect()
+    }
+    fn validate(&self, screens: &ScreenRegistry) -> Vec<ScreenProblem> {
+        let mut problems = Vec::new();
+        for (exit, link) in self.exits.iter().enumerate() {
+            match link {
+                Some(link) => problems.extend(validate_link(screens, Some(exit), link)),
+                None if self.map.has_special_tile(4 + exit) => {
+                    problems.push(ScreenProblem::UnlinkedExit(exit));
+                }
+                None => {}
+            }
+        }
+        if self.map.has_special_tile(12) && self.map.find_special_tile(7).is_none() {
+            problems.push(ScreenProblem::ResetWithoutSpawn);
+        }
+        // a standing player takes up the checkpoint's tile and the one above it
+        for (x, y) in self.map.special_tiles(13) {
+            let blocked = |y: usize| self.map.get_collision_tile(x, y) == CollisionTile::Solid;
+            if blocked(y) || (y > 0 && blocked(y - 1)) {
+                problems.push(ScreenProblem::BlockedCheckpoint(x, y));
+            }
+        }
+        problems
     }
     fn update(&mut self, mut ctx: ScreenUpdateContext) -> ScreenUpdateResult {
+        let solids: Vec<_> = self
+            .entities
+            .iter_mut()
+            .filter_map(|entity| entity.update_solid())
+            .collect();
+        let world = World {
+            map: &self.map,
+            solids: &solids,
+            config: ctx.config,
+            nav: self.nav.as_ref(),
+        };
         for entity in self.entities.iter_mut() {
-            entity.update(&self.map, &mut ctx);
+            entity.update(&world, &mut ctx);
+        }
+        self.entities.retain(|entity| !entity.removed());
+        ctx.player.update(&world, ctx.input);
+        if ctx.player.should_respawn() {
+            ctx.player.respawn(self.respawn_point);
+            self.camera.snap(ctx.player.pos, ctx.player.facing_right);
         }
-        ctx.player.update(&self.map);
+        self.camera.update(ctx.player.pos, ctx.player.facing_right);
 
         // handle special tiles
 
         let tile_pos = (ctx.player.pos / 8.0).round();
         let tile = self.map.get_special_tile(tile_pos.x as _, tile_pos.y as _);
         if (4..=7).contains(&tile) {
-            let l = self.linked_screens.len();
-            if l <= tile - 4 {
-                panic!(
-                    "Attempt to load linked screen #{}, but there's only {} linked screens!",
-                    tile - 4,
-                    l
-                );
-            }
-            let target = self.linked_screens[tile - 4];
-            return ScreenUpdateResult::ChangeScreen(target.0, target.1);
+            let Some(target) = &self.exits[tile - 4] else {
+                panic!("Attempt to take exit #{}, but it isn't linked!", tile - 4);
+            };
+            return ScreenUpdateResult::ChangeScreen(target.clone());
         }
         if tile == 12 {
             let p = self.map.find_special_tile(7).unwrap();
-            ctx.player.pos = Vec2::new(p.0 as f32 * 8.0, p.1 as f32 * 8.0)
+            ctx.player.pos = Vec2::new(p.0 as f32 * 8.0, p.1 as f32 * 8.0);
+            ctx.player.last_pos = ctx.player.pos;
+        }
+        // checkpoint
+        if tile == 13 {
+            self.respawn_point = (tile_pos * 8.0).floor();
         }
         ScreenUpdateResult::Pass
     }
-    fn draw(&mut self, mut ctx: ScreenUpdateContext) {
+    fn draw(&mut self, mut ctx: ScreenUpdateContext, alpha: f32) {
+        let view = self.camera.view(alpha);
+        ctx.render_layers.set_view(view);
+        if self.drawn_view != Some(view) {
+            self.drawn_view = Some(view);
+            self.map.draw(&mut ctx, view);
+        }
         for layer in ctx.render_layers.get_redrawn() {
             layer.calls.push(DrawCall::Clear(BLACK.with_alpha(0.0)));
         }
         for entity in self.entities.iter() {
-            entity.draw(&mut ctx);
+            entity.draw(&mut ctx, alpha);
+        }
+        ctx.player.draw(&mut ctx.render_layers.entities, alpha);
+        ctx.player.draw_ui(&mut ctx.render_layers.ui);
+        if ctx.debug_overlay {
+            debug::draw_overlay(
+                &mut ctx.render_layers.ui,
+                view,
+                &self.map,
+                &self.entities,
+                ctx.player,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::Action;
+
+    /// Runs `screen` for a tick with `actions` held.
+    fn tick(screen: &mut dyn Screen, player: &mut Player, actions: &[Action]) {
+        let config = MovementConfig::built_in();
+        let mut render_layers = RenderLayers::null();
+        let held = actions.iter().copied().collect();
+        screen.update(ScreenUpdateContext {
+            player,
+            render_layers: &mut render_layers,
+            config: &config,
+            debug_overlay: false,
+            input: Input::from_held(held, held),
+        });
+    }
+    /// Kills the player, then runs `screen` until they've respawned.
+    fn die(screen: &mut dyn Screen, player: &mut Player) {
+        // respawning leaves the player invulnerable for a while
+        while player.invulnerable_ticks > 0 {
+            tick(screen, player, &[]);
+        }
+        player.damage(player.max_health, Vec2::ZERO);
+        assert!(player.is_dead());
+        for _ in 0..200 {
+            tick(screen, player, &[]);
+            if !player.is_dead() {
+                return;
+            }
+        }
+        panic!("never respawned");
+    }
+
+    #[test]
+    fn respawns_at_the_last_checkpoint() {
+        let map = Map::from_ascii(
+            "
+            ............
+            ............
+            S.....C.....
+            ############
+            ",
+        );
+        let mut screen = TilemapScreen::new("test.tmx", map).unwrap();
+        let mut player = Player::new();
+        let mut render_layers = RenderLayers::null();
+        let config = MovementConfig::built_in();
+        screen.on_load(
+            ScreenUpdateContext {
+                player: &mut player,
+                render_layers: &mut render_layers,
+                config: &config,
+                debug_overlay: false,
+                input: Input::default(),
+            },
+            "0",
+        );
+        let spawn = Vec2::new(0.0, 16.0);
+        assert_eq!(player.pos, spawn);
+
+        // before touching the checkpoint, the player comes back where they entered
+        die(&mut screen, &mut player);
+        assert_eq!(player.pos, spawn);
+
+        while player.pos.x < 64.0 {
+            tick(&mut screen, &mut player, &[Action::MoveRight]);
+        }
+        die(&mut screen, &mut player);
+        assert_eq!(player.pos, Vec2::new(48.0, 16.0));
+        assert_eq!(player.health, player.max_health);
+    }
+
+    #[test]
+    fn checkpoints_inside_walls_are_found() {
+        let map = Map::from_ascii(
+            "
+            ......#.....
+            S.....C.....
+            ############
+            ",
+        );
+        let screen = TilemapScreen::new("test.tmx", map).unwrap();
+        let problems = screen.validate(&ScreenRegistry::new());
+        assert!(matches!(
+            problems[..],
+            [ScreenProblem::BlockedCheckpoint(6, 1)]
+        ));
+    }
+
+    /// Screens named by the first of each pair, each with spawn 0 and exit 0, and the
+    /// exit leading to the second if anywhere.
+    fn linked_screens(links: &[(&str, Option<&str>)]) -> ScreenRegistry {
+        let mut screens = ScreenRegistry::new();
+        for (name, exit) in links {
+            let mut map = Map::from_ascii("S..E\n####");
+            if let Some(exit) = exit {
+                map.properties
+                    .0
+                    .insert("exit0".to_string(), exit.to_string());
+            }
+            let screen = TilemapScreen::new(&format!("{name}.tmx"), map).unwrap();
+            screens.insert(name.to_string(), Box::new(screen));
         }
-        ctx.player.draw(&mut ctx.render_layers.entities);
+        screens
+    }
+
+    #[test]
+    fn screen_files_match_the_screens_folder() {
+        let folder = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/screens");
+        let mut on_disk: Vec<String> = std::fs::read_dir(folder)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .filter(|name| name.ends_with(".tmx"))
+            .collect();
+        on_disk.sort();
+        let mut listed: Vec<&str> = SCREEN_FILES.iter().map(|(name, _)| *name).collect();
+        listed.sort();
+        assert_eq!(on_disk, listed);
+    }
+
+    #[test]
+    fn broken_links_are_found() {
+        let screens = linked_screens(&[
+            ("a", Some("b:0")),
+            ("b", Some("a:nowhere")),
+            ("c", None),
+            ("d", Some("e:0")),
+        ]);
+        let problems = validate_screens(&screens, &ScreenLink::new("a", "0"));
+        let problems: Vec<_> = problems
+            .iter()
+            .map(|(screen, problem)| (screen.as_str(), problem))
+            .collect();
+        assert!(
+            matches!(
+                problems[..],
+                [
+                    ("b", ScreenProblem::MissingSpawn(Some(0), _)),
+                    ("c", ScreenProblem::UnlinkedExit(0)),
+                    ("d", ScreenProblem::UnknownScreen(Some(0), _)),
+                ]
+            ),
+            "{problems:?}"
+        );
+
+        let problems = validate_screens(&screens, &ScreenLink::new("a", "nowhere"));
+        assert!(matches!(
+            problems[0],
+            (_, ScreenProblem::MissingSpawn(None, _))
+        ));
+    }
+
+    #[test]
+    fn screens_without_a_way_in_are_unreachable() {
+        let screens = linked_screens(&[
+            ("a", Some("b:0")),
+            ("b", Some("a:0")),
+            ("c", Some("a:0")),
+            ("d", None),
+        ]);
+        let start = ScreenLink::new("a", "0");
+        assert_eq!(unreachable_screens(&screens, &start), ["c", "d"]);
+        assert_eq!(
+            unreachable_screens(&screens, &ScreenLink::new("c", "0")),
+            ["d"]
+        );
     }
 }
@@ -0,0 +1,590 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    io::{self, Read},
+    str::FromStr,
+};
+
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use flate2::read::{GzDecoder, ZlibDecoder};
+use roxmltree::{Document, Node};
+use ruzstd::decoding::StreamingDecoder;
+
+/// Tiled stores flip/rotation flags in the top bits of every gid.
+const GID_FLAGS: u32 = 0xE000_0000;
+
+/// A parsed Tiled `.tmx` file.
+///
+/// Only orthogonal, finite maps are supported.
+pub struct TmxMap {
+    pub width: usize,
+    pub height: usize,
+    pub tile_width: usize,
+    pub tile_height: usize,
+    pub properties: Properties,
+    pub tilesets: Vec<TilesetRef>,
+    pub layers: Vec<TileLayer>,
+    pub object_groups: Vec<ObjectGroup>,
+}
+
+pub struct TilesetRef {
+    pub first_gid: u32,
+    /// Path of an external `.tsx` tileset, relative to the map.
+    pub source: Option<String>,
+    /// Name of an embedded tileset.
+    pub name: Option<String>,
+}
+
+pub struct TileLayer {
+    pub id: u32,
+    pub name: String,
+    pub width: usize,
+    pub height: usize,
+    pub properties: Properties,
+    /// Tile gids, row by row. `0` is an empty tile.
+    pub tiles: Vec<usize>,
+}
+
+pub struct ObjectGroup {
+    pub id: u32,
+    pub name: String,
+    pub properties: Properties,
+    pub objects: Vec<Object>,
+}
+
+pub struct Object {
+    pub id: u32,
+    pub name: String,
+    /// The object's `type`, called `class` by some versions of Tiled.
+    pub class: String,
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    /// Points of a polyline or polygon object, relative to `x` and `y`
+    pub points: Vec<(f32, f32)>,
+    /// Whether `points` form a polygon, which joins back up with its start
+    pub closed: bool,
+    pub properties: Properties,
+}
+impl Object {
+    /// Parses a custom property, falling back to `default` if it isn't set.
+    pub fn property<T: FromStr>(&self, name: &str, default: T) -> Result<T, TmxErrorKind> {
+        match self.properties.get(name) {
+            Some(value) => value.parse().map_err(|_| TmxErrorKind::InvalidProperty {
+                object: self.id,
+                name: name.to_string(),
+                value: value.to_string(),
+            }),
+            None => Ok(default),
+        }
+    }
+}
+
+/// A parsed Tiled `.tsx` tileset.
+pub struct Tileset {
+    pub tile_count: usize,
+    pub columns: usize,
+    /// Custom properties of individual tiles, by id within the tileset
+    pub tiles: HashMap<u32, Properties>,
+}
+impl Tileset {
+    /// Parses a tileset. `file` is only used to label errors.
+    pub fn parse(file: &str, xml: &str) -> Result<Self, TmxError> {
+        let error = |kind| TmxError {
+            file: file.to_string(),
+            layer: None,
+            kind,
+        };
+        let doc = Document::parse(xml).map_err(|e| error(TmxErrorKind::Xml(e)))?;
+        let root = doc.root_element();
+        if root.tag_name().name() != "tileset" {
+            return Err(error(TmxErrorKind::UnexpectedRoot(
+                "tileset",
+                root.tag_name().name().to_string(),
+            )));
+        }
+        let mut tiles = HashMap::new();
+        for tile in root.children().filter(|n| n.has_tag_name("tile")) {
+            tiles.insert(
+                attribute(tile, "id").map_err(error)?,
+                parse_properties(tile),
+            );
+        }
+        Ok(Self {
+            tile_count: attribute(root, "tilecount").map_err(error)?,
+            columns: attribute(root, "columns").map_err(error)?,
+            tiles,
+        })
+    }
+}
+
+/// Custom properties, with values kept as they're written in the file.
+#[derive(Default)]
+pub struct Properties(pub HashMap<String, String>);
+impl Properties {
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0.get(name).map(String::as_str)
+    }
+}
+
+#[derive(Debug)]
+pub struct TmxError {
+    /// The map file that failed to load.
+    pub file: String,
+    /// The layer being read, if the error is specific to one.
+    pub layer: Option<String>,
+    pub kind: TmxErrorKind,
+}
+
+#[derive(Debug)]
+pub enum TmxErrorKind {
+    Xml(roxmltree::Error),
+    /// The root element, as the tag it should be and the tag it was
+    UnexpectedRoot(&'static str, String),
+    MissingAttribute(String, &'static str),
+    InvalidAttribute(&'static str, String),
+    MissingData,
+    MissingLayer,
+    UnsupportedEncoding(String),
+    UnsupportedCompression(String),
+    InvalidBase64(base64::DecodeError),
+    Decompression(io::Error),
+    /// Binary tile data whose length isn't a whole number of 32-bit gids.
+    TruncatedData(usize),
+    InfiniteMap,
+    InvalidTile(String),
+    UnknownObjectType {
+        object: u32,
+        class: String,
+    },
+    InvalidProperty {
+        object: u32,
+        name: String,
+        value: String,
+    },
+    InvalidMapProperty {
+        name: String,
+        value: String,
+    },
+    InvalidTileProperty {
+        tile: u32,
+        name: String,
+        value: String,
+    },
+    WrongTileCount {
+        expected: usize,
+        found: usize,
+    },
+    /// A layer whose size differs from the map's, as `(width, height)`.
+    WrongLayerSize {
+        expected: (usize, usize),
+        found: (usize, usize),
+    },
+}
+
+impl fmt::Display for TmxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.file)?;
+        if let Some(layer) = &self.layer {
+            write!(f, " (layer \"{layer}\")")?;
+        }
+        write!(f, ": ")?;
+        match &self.kind {
+            TmxErrorKind::Xml(e) => write!(f, "invalid xml: {e}"),
+            TmxErrorKind::UnexpectedRoot(expected, tag) => {
+                write!(f, "expected <{expected}>, found <{tag}>")
+            }
+            TmxErrorKind::MissingAttribute(element, attribute) => {
+                write!(f, "<{element}> is missing attribute \"{attribute}\"")
+            }
+            TmxErrorKind::InvalidAttribute(attribute, value) => {
+                write!(f, "invalid value \"{value}\" for attribute \"{attribute}\"")
+            }
+            TmxErrorKind::MissingData => write!(f, "layer has no <data>"),
+            TmxErrorKind::MissingLayer => write!(f, "no such layer"),
+            TmxErrorKind::UnsupportedEncoding(encoding) => {
+                write!(f, "unsupported tile data encoding \"{encoding}\"")
+            }
+            TmxErrorKind::UnsupportedCompression(compression) => {
+                write!(f, "unsupported tile data compression \"{compression}\"")
+            }
+            TmxErrorKind::InvalidBase64(e) => write!(f, "invalid base64 tile data: {e}"),
+            TmxErrorKind::Decompression(e) => write!(f, "couldn't decompress tile data: {e}"),
+            TmxErrorKind::TruncatedData(len) => {
+                write!(f, "tile data is {len} bytes, which isn't a multiple of 4")
+            }
+            TmxErrorKind::InfiniteMap => write!(f, "infinite maps aren't supported"),
+            TmxErrorKind::InvalidTile(tile) => write!(f, "invalid tile \"{tile}\""),
+            TmxErrorKind::UnknownObjectType { object, class } => {
+                write!(f, "object {object} has unknown type \"{class}\"")
+            }
+            TmxErrorKind::InvalidProperty {
+                object,
+                name,
+                value,
+            } => write!(
+                f,
+                "object {object} has invalid value \"{value}\" for property \"{name}\""
+            ),
+            TmxErrorKind::InvalidTileProperty { tile, name, value } => write!(
+                f,
+                "tile {tile} has invalid value \"{value}\" for property \"{name}\""
+            ),
+            TmxErrorKind::InvalidMapProperty { name, value } => {
+                write!(f, "invalid value \"{value}\" for map property \"{name}\"")
+            }
+            TmxErrorKind::WrongTileCount { expected, found } => {
+                write!(f, "expected {expected} tiles, found {found}")
+            }
+            TmxErrorKind::WrongLayerSize { expected, found } => write!(
+                f,
+                "layer is {}x{}, but the map is {}x{}",
+                found.0, found.1, expected.0, expected.1
+            ),
+        }
+    }
+}
+impl std::error::Error for TmxError {}
+
+impl TmxMap {
+    /// Parses a map. `file` is only used to label errors.
+    pub fn parse(file: &str, xml: &str) -> Result<Self, TmxError> {
+        let error = |layer: Option<&str>, kind| TmxError {
+            file: file.to_string(),
+            layer: layer.map(str::to_string),
+            kind,
+        };
+        let doc = Document::parse(xml).map_err(|e| error(None, TmxErrorKind::Xml(e)))?;
+        let root = doc.root_element();
+        if root.tag_name().name() != "map" {
+            return Err(error(
+                None,
+                TmxErrorKind::UnexpectedRoot("map", root.tag_name().name().to_string()),
+            ));
+        }
+        if root.attribute("infinite") == Some("1") {
+            return Err(error(None, TmxErrorKind::InfiniteMap));
+        }
+
+        let mut map = Self {
+            width: attribute(root, "width").map_err(|k| error(None, k))?,
+            height: attribute(root, "height").map_err(|k| error(None, k))?,
+            tile_width: attribute(root, "tilewidth").map_err(|k| error(None, k))?,
+            tile_height: attribute(root, "tileheight").map_err(|k| error(None, k))?,
+            properties: parse_properties(root),
+            tilesets: Vec::new(),
+            layers: Vec::new(),
+            object_groups: Vec::new(),
+        };
+        for node in root.children().filter(Node::is_element) {
+            if node.tag_name().name() == "tileset" {
+                map.tilesets.push(TilesetRef {
+                    first_gid: attribute(node, "firstgid").map_err(|k| error(None, k))?,
+                    source: node.attribute("source").map(str::to_string),
+                    name: node.attribute("name").map(str::to_string),
+                });
+            }
+        }
+        map.parse_layers(root, &error)?;
+        Ok(map)
+    }
+    /// Reads every layer and object group below `parent`, flattening `<group>`s.
+    fn parse_layers(
+        &mut self,
+        parent: Node,
+        error: &impl Fn(Option<&str>, TmxErrorKind) -> TmxError,
+    ) -> Result<(), TmxError> {
+        for node in parent.children().filter(Node::is_element) {
+            let name = node.attribute("name").unwrap_or_default();
+            let e = |kind| error(Some(name), kind);
+            match node.tag_name().name() {
+                "layer" => {
+                    let width = attribute(node, "width").map_err(e)?;
+                    let height = attribute(node, "height").map_err(e)?;
+                    let data = node
+                        .children()
+                        .find(|n| n.has_tag_name("data"))
+                        .ok_or_else(|| e(TmxErrorKind::MissingData))?;
+                    let tiles = parse_data(data).map_err(e)?;
+                    if tiles.len() != width * height {
+                        return Err(e(TmxErrorKind::WrongTileCount {
+                            expected: width * height,
+                            found: tiles.len(),
+                        }));
+                    }
+                    self.layers.push(TileLayer {
+                        id: attribute(node, "id").map_err(e)?,
+                        name: name.to_string(),
+                        width,
+                        height,
+                        properties: parse_properties(node),
+                        tiles,
+                    });
+                }
+                "objectgroup" => {
+                    let mut objects = Vec::new();
+                    for object in node.children().filter(|n| n.has_tag_name("object")) {
+                        let shape = object
+                            .children()
+                            .find(|n| n.has_tag_name("polyline") || n.has_tag_name("polygon"));
+                        let points = match shape {
+                            Some(shape) => parse_points(shape).map_err(e)?,
+                            None => Vec::new(),
+                        };
+                        objects.push(Object {
+                            id: attribute(object, "id").map_err(e)?,
+                            name: object.attribute("name").unwrap_or_default().to_string(),
+                            class: object
+                                .attribute("type")
+                                .or(object.attribute("class"))
+                                .unwrap_or_default()
+                                .to_string(),
+                            x: attribute(object, "x").map_err(e)?,
+                            y: attribute(object, "y").map_err(e)?,
+                            width: optional_attribute(object, "width").map_err(e)?,
+                            height: optional_attribute(object, "height").map_err(e)?,
+                            points,
+                            closed: shape.is_some_and(|n| n.has_tag_name("polygon")),
+                            properties: parse_properties(object),
+                        });
+                    }
+                    self.object_groups.push(ObjectGroup {
+                        id: attribute(node, "id").map_err(e)?,
+                        name: name.to_string(),
+                        properties: parse_properties(node),
+                        objects,
+                    });
+                }
+                "group" => self.parse_layers(node, error)?,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+    /// Finds a tile layer by name, erroring if it doesn't exist.
+    pub fn tile_layer(&self, file: &str, name: &str) -> Result<&TileLayer, TmxError> {
+        self.layers
+            .iter()
+            .find(|layer| layer.name == name)
+            .ok_or_else(|| TmxError {
+                file: file.to_string(),
+                layer: Some(name.to_string()),
+                kind: TmxErrorKind::MissingLayer,
+            })
+    }
+}
+
+fn attribute<T: FromStr>(node: Node, name: &'static str) -> Result<T, TmxErrorKind> {
+    let value = node
+        .attribute(name)
+        .ok_or_else(|| TmxErrorKind::MissingAttribute(node.tag_name().name().to_string(), name))?;
+    value
+        .parse()
+        .map_err(|_| TmxErrorKind::InvalidAttribute(name, value.to_string()))
+}
+fn optional_attribute<T: FromStr + Default>(
+    node: Node,
+    name: &'static str,
+) -> Result<T, TmxErrorKind> {
+    match node.attribute(name) {
+        Some(_) => attribute(node, name),
+        None => Ok(T::default()),
+    }
+}
+
+/// Reads the `points` of a polyline or polygon, written as `x,y x,y ...`.
+fn parse_points(node: Node) -> Result<Vec<(f32, f32)>, TmxErrorKind> {
+    let value: String = attribute(node, "points")?;
+    value
+        .split_whitespace()
+        .map(|point| {
+            let (x, y) = point.split_once(',')?;
+            Some((x.parse().ok()?, y.parse().ok()?))
+        })
+        .collect::<Option<_>>()
+        .ok_or(TmxErrorKind::InvalidAttribute("points", value))
+}
+
+fn parse_properties(node: Node) -> Properties {
+    let mut properties = Properties::default();
+    let Some(list) = node.children().find(|n| n.has_tag_name("properties")) else {
+        return properties;
+    };
+    for property in list.children().filter(|n| n.has_tag_name("property")) {
+        let Some(name) = property.attribute("name") else {
+            continue;
+        };
+        // multiline strings are stored as text instead of in `value`
+        let value = property
+            .attribute("value")
+            .or(property.text())
+            .unwrap_or_default();
+        properties.0.insert(name.to_string(), value.to_string());
+    }
+    properties
+}
+
+fn parse_data(data: Node) -> Result<Vec<usize>, TmxErrorKind> {
+    let encoding = data.attribute("encoding");
+    let compression = data.attribute("compression");
+    let gids: Vec<u32> = match (encoding, compression) {
+        (Some("csv"), None) => data
+            .text()
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|tile| !tile.is_empty())
+            .map(|tile| {
+                tile.parse()
+                    .map_err(|_| TmxErrorKind::InvalidTile(tile.to_string()))
+            })
+            .collect::<Result<_, _>>()?,
+        (Some("base64"), compression) => {
+            let bytes = BASE64
+                .decode(data.text().unwrap_or_default().trim())
+                .map_err(TmxErrorKind::InvalidBase64)?;
+            let bytes = decompress(&bytes, compression)?;
+            if bytes.len() % 4 != 0 {
+                return Err(TmxErrorKind::TruncatedData(bytes.len()));
+            }
+            bytes
+                .chunks_exact(4)
+                .map(|gid| u32::from_le_bytes([gid[0], gid[1], gid[2], gid[3]]))
+                .collect()
+        }
+        (None, None) => data
+            .children()
+            .filter(|n| n.has_tag_name("tile"))
+            .map(|tile| optional_attribute(tile, "gid"))
+            .collect::<Result<_, _>>()?,
+        (None | Some("csv"), Some(compression)) => {
+            return Err(TmxErrorKind::UnsupportedCompression(
+                compression.to_string(),
+            ));
+        }
+        (Some(other), _) => return Err(TmxErrorKind::UnsupportedEncoding(other.to_string())),
+    };
+    Ok(gids
+        .into_iter()
+        .map(|gid| (gid & !GID_FLAGS) as usize)
+        .collect())
+}
+
+/// Decompresses base64-decoded tile data according to the `compression` attribute.
+fn decompress(bytes: &[u8], compression: Option<&str>) -> Result<Vec<u8>, TmxErrorKind> {
+    let mut out = Vec::new();
+    let result = match compression {
+        None => return Ok(bytes.to_vec()),
+        Some("zlib") => ZlibDecoder::new(bytes).read_to_end(&mut out),
+        Some("gzip") => GzDecoder::new(bytes).read_to_end(&mut out),
+        Some("zstd") => StreamingDecoder::new(bytes)
+            .map_err(io::Error::other)
+            .and_then(|mut decoder| decoder.read_to_end(&mut out)),
+        Some(other) => return Err(TmxErrorKind::UnsupportedCompression(other.to_string())),
+    };
+    result.map_err(TmxErrorKind::Decompression)?;
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::{
+        Compression,
+        write::{GzEncoder, ZlibEncoder},
+    };
+    use ruzstd::encoding::{CompressionLevel, compress_to_vec};
+
+    use super::*;
+    use crate::screens::SCREEN_FILES;
+
+    fn compress(bytes: &[u8], compression: Option<&str>) -> Vec<u8> {
+        match compression {
+            None => bytes.to_vec(),
+            Some("zlib") => {
+                let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(bytes).unwrap();
+                encoder.finish().unwrap()
+            }
+            Some("gzip") => {
+                let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(bytes).unwrap();
+                encoder.finish().unwrap()
+            }
+            Some("zstd") => compress_to_vec(bytes, CompressionLevel::Fastest),
+            Some(other) => panic!("no encoder for {other}"),
+        }
+    }
+
+    /// Rewrites every csv `<data>` block of a map the way Tiled would save it
+    /// with the given compression.
+    fn reencode(xml: &str, compression: Option<&str>) -> String {
+        let mut out = String::new();
+        let mut rest = xml;
+        while let Some((before, after)) = rest.split_once("<data encoding=\"csv\">") {
+            let (csv, after) = after.split_once("</data>").unwrap();
+            let bytes: Vec<u8> = csv
+                .split(',')
+                .flat_map(|tile| tile.trim().parse::<u32>().unwrap().to_le_bytes())
+                .collect();
+            let attributes = match compression {
+                Some(compression) => format!("encoding=\"base64\" compression=\"{compression}\""),
+                None => "encoding=\"base64\"".to_string(),
+            };
+            out += before;
+            out += &format!(
+                "<data {attributes}>\n   {}\n  </data>",
+                BASE64.encode(compress(&bytes, compression))
+            );
+            rest = after;
+        }
+        out + rest
+    }
+
+    #[test]
+    fn encodings_match_csv() {
+        for (file, xml) in SCREEN_FILES {
+            let csv = TmxMap::parse(file, xml).unwrap();
+            for compression in [None, Some("zlib"), Some("gzip"), Some("zstd")] {
+                let encoded = reencode(xml, compression);
+                assert!(!encoded.contains("encoding=\"csv\""));
+                let map = TmxMap::parse(file, &encoded).unwrap();
+                assert_eq!(map.layers.len(), csv.layers.len());
+                for (a, b) in csv.layers.iter().zip(map.layers.iter()) {
+                    assert_eq!(a.name, b.name);
+                    assert!(
+                        a.tiles == b.tiles,
+                        "{file} layer {} differs with {compression:?}",
+                        a.name
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn unknown_compression_is_an_error() {
+        let (file, xml) = SCREEN_FILES[0];
+        let xml = reencode(xml, None).replace(
+            "encoding=\"base64\"",
+            "encoding=\"base64\" compression=\"lz4\"",
+        );
+        let error = TmxMap::parse(file, &xml).err().unwrap();
+        assert_eq!(error.layer.as_deref(), Some("Background"));
+        assert!(matches!(
+            error.kind,
+            TmxErrorKind::UnsupportedCompression(ref c) if c == "lz4"
+        ));
+    }
+
+    #[test]
+    fn a_tileset_with_the_wrong_root_says_so() {
+        let error = Tileset::parse("bad.tsx", "<map/>").err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "bad.tsx: expected <tileset>, found <map>"
+        );
+    }
+}
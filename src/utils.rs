@@ -3,15 +3,18 @@ use std::marker::PhantomData;
 use enum_iterator::Sequence;
 use macroquad::prelude::*;
 
-
 pub const SCREEN_WIDTH: f32 = 384.0;
 pub const SCREEN_HEIGHT: f32 = 216.0;
 
-pub const MAX_RUN_VELOCITY: f32 = 2.2;
-pub const GROUND_FRICTION: f32 = 0.21;
-pub const PLAYER_SPEED: f32 = 2.0;
-pub const AIR_DRAG: f32 = 0.07;
-pub const GRAVITY: f32 = 0.9;
+/// Tiles per row of the tileset
+pub const TILESET_COLUMNS: usize = 64;
+/// Number of tiles in the tileset
+pub const TILESET_TILES: usize = 4096;
+
+/// Length of one simulation tick, in seconds
+pub const TICK_DURATION: f64 = 1.0 / 60.0;
+/// Most ticks run in a single frame, so a long hitch doesn't stall the game catching up
+pub const MAX_TICKS_PER_FRAME: u32 = 5;
 
 pub fn create_camera(w: f32, h: f32) -> Camera2D {
     let rt = render_target(w as u32, h as u32);
@@ -44,7 +47,4 @@ impl<A: Sequence + Into<usize>, T> Registry<A, T> {
     pub fn get(&self, id: A) -> &T {
         &self.values[id.into()]
     }
-    pub fn get_mut(&mut self, id: A) -> &mut T {
-        &mut self.values[id.into()]
-    }
 }